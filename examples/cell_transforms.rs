@@ -0,0 +1,48 @@
+use bevy::math::vec2;
+use bevy::prelude::*;
+use bevy_sprite_grid::prelude::*;
+
+fn spawn_grid(mut commands: Commands) {
+    let cell_size = vec2(32.0, 32.0);
+    let sprite_grid = SpriteGrid::from_fn(
+        ([12, 12], cell_size, SpriteGridAlignment::center()),
+        |[x, y]| {
+            if (x + y) % 2 == 0 {
+                Color::ORANGE
+            } else {
+                Color::WHITE
+            }
+            .into()
+        },
+    );
+    commands.spawn_bundle(SpriteGridBundle {
+        sprite_grid,
+        ..Default::default()
+    });
+}
+
+fn wobble(time: Res<Time>, mut query: Query<&mut SpriteGrid>) {
+    let t = time.seconds_since_startup() as f32;
+    query.for_each_mut(|mut sprite_grid| {
+        for x in 0..sprite_grid.x_len() {
+            for y in 0..sprite_grid.y_len() {
+                let phase = t + 0.3 * (x + y) as f32;
+                let cell_transform = sprite_grid.cell_transform_mut([x, y]);
+                cell_transform.rotation = Quat::from_rotation_z(0.25 * phase.sin());
+                cell_transform.scale = Vec3::splat(0.8 + 0.2 * phase.cos());
+            }
+        }
+    });
+}
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(SpriteGridPlugin)
+        .add_startup_system(|mut commands: Commands| {
+            commands.spawn_bundle(Camera2dBundle::default());
+        })
+        .add_startup_system(spawn_grid)
+        .add_system(wobble)
+        .run();
+}
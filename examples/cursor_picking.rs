@@ -0,0 +1,91 @@
+use bevy::math::vec2;
+use bevy::prelude::*;
+use bevy_sprite_grid::prelude::*;
+
+const COLORS: [Color; 3] = [Color::MAROON, Color::TEAL, Color::GOLD];
+
+fn spawn_grid(mut commands: Commands) {
+    let sprite_grid = SpriteGrid::from_fn(
+        ([16, 12], vec2(40.0, 40.0), SpriteGridAlignment::center()),
+        |[x, y]| Some(COLORS[(x * 7 + y * 13 + x * y) % COLORS.len()]),
+    );
+    commands.spawn_bundle(SpriteGridBundle {
+        sprite_grid,
+        ..Default::default()
+    });
+}
+
+fn highlight(
+    mut hovered: EventReader<CellHovered>,
+    mut exited: EventReader<CellHoverExited>,
+    mut grids: Query<&mut SpriteGrid>,
+) {
+    for event in exited.iter() {
+        if let Ok(mut grid) = grids.get_mut(event.grid) {
+            grid.remove_cell_transform(event.cell);
+        }
+    }
+    for event in hovered.iter() {
+        if let Ok(mut grid) = grids.get_mut(event.grid) {
+            grid.set_cell_transform(event.cell, Transform::from_scale(Vec3::splat(0.8)));
+        }
+    }
+}
+
+/// Left click bucket fills the clicked region with the next color,
+/// dragging with the right button paints cells white.
+fn paint(
+    mut clicked: EventReader<CellClicked>,
+    mut dragged: EventReader<CellDragged>,
+    mut grids: Query<&mut SpriteGrid>,
+) {
+    for event in clicked.iter() {
+        if let Ok(mut grid) = grids.get_mut(event.grid) {
+            match event.button {
+                MouseButton::Left => {
+                    let color = match &grid[event.cell] {
+                        Some(SpriteCell::Color(color)) => *color,
+                        _ => continue,
+                    };
+                    let next = COLORS
+                        .iter()
+                        .position(|&c| c == color)
+                        .map_or(COLORS[0], |i| COLORS[(i + 1) % COLORS.len()]);
+                    grid.flood_fill(
+                        event.cell,
+                        |_, cell| matches!(cell, Some(SpriteCell::Color(c)) if *c == color),
+                        Some(next.into()),
+                        SpriteGridConnectivity::Four,
+                    );
+                }
+                MouseButton::Right => grid.set(event.cell, Color::WHITE),
+                _ => {}
+            }
+        }
+    }
+    for event in dragged.iter() {
+        if event.button == MouseButton::Right {
+            if let Ok(mut grid) = grids.get_mut(event.grid) {
+                grid.draw_line(
+                    event.from.map(|i| i as i64),
+                    event.cell.map(|i| i as i64),
+                    Some(Color::WHITE.into()),
+                );
+            }
+        }
+    }
+}
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(SpriteGridPlugin)
+        .add_plugin(SpriteGridPickingPlugin)
+        .add_startup_system(|mut commands: Commands| {
+            commands.spawn_bundle(Camera2dBundle::default());
+        })
+        .add_startup_system(spawn_grid)
+        .add_system(highlight)
+        .add_system(paint)
+        .run();
+}
@@ -0,0 +1,67 @@
+use bevy::math::vec2;
+use bevy::prelude::*;
+use bevy_sprite_grid::prelude::*;
+
+const COLORS: [Color; 3] = [Color::MAROON, Color::TEAL, Color::GOLD];
+
+// untextured cells are drawn as rectangles the size of each hexagon's
+// bounding box, shrink them so the rows don't overlap
+const CELL_SCALE: Vec3 = Vec3::new(0.9, 0.7, 1.0);
+
+fn spawn_grid(mut commands: Commands) {
+    let mut sprite_grid = SpriteGrid::empty((
+        [15, 13],
+        vec2(40.0, 46.0),
+        SpriteGridAlignment::center(),
+        SpriteGridLayout::Hex(HexLayout::pointy_top(HexCoordinates::OddOffset)),
+    ));
+    let center = sprite_grid.cell_to_axial([7, 6]).unwrap();
+    for y in 0..sprite_grid.y_len() {
+        for x in 0..sprite_grid.x_len() {
+            let [q, r] = sprite_grid.cell_to_axial([x, y]).unwrap();
+            let [dq, dr] = [q - center[0], r - center[1]];
+            let distance = (dq.abs() + dr.abs() + (dq + dr).abs()) / 2;
+            sprite_grid.set([x, y], COLORS[distance as usize % COLORS.len()]);
+            sprite_grid.set_cell_transform([x, y], Transform::from_scale(CELL_SCALE));
+        }
+    }
+    commands.spawn_bundle(SpriteGridBundle {
+        sprite_grid,
+        ..Default::default()
+    });
+}
+
+/// Lifts the hovered cell and its neighbors.
+fn highlight(
+    mut hovered: EventReader<CellHovered>,
+    mut exited: EventReader<CellHoverExited>,
+    mut grids: Query<&mut SpriteGrid>,
+) {
+    let exited = exited
+        .iter()
+        .map(|event| (event.grid, event.cell, CELL_SCALE));
+    let hovered = hovered
+        .iter()
+        .map(|event| (event.grid, event.cell, 1.2 * CELL_SCALE));
+    for (grid, cell, scale) in exited.chain(hovered) {
+        if let Ok(mut grid) = grids.get_mut(grid) {
+            let neighbors: Vec<_> = grid.hex_neighbors(cell).map(|(n, _)| n).collect();
+            for cell in neighbors.into_iter().chain([cell]) {
+                grid.set_cell_transform(cell, Transform::from_scale(scale));
+            }
+        }
+    }
+}
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(SpriteGridPlugin)
+        .add_plugin(SpriteGridPickingPlugin)
+        .add_startup_system(|mut commands: Commands| {
+            commands.spawn_bundle(Camera2dBundle::default());
+        })
+        .add_startup_system(spawn_grid)
+        .add_system(highlight)
+        .run();
+}
@@ -0,0 +1,77 @@
+use bevy::math::vec2;
+use bevy::prelude::*;
+use bevy_sprite_grid::prelude::*;
+use std::f32::consts::FRAC_1_SQRT_2;
+use std::f32::consts::FRAC_PI_4;
+
+fn cell_color([x, y]: [usize; 2]) -> Color {
+    if (x + y) % 2 == 0 {
+        Color::DARK_GREEN
+    } else {
+        Color::OLIVE
+    }
+}
+
+fn spawn_grids(mut commands: Commands) {
+    // untextured cells are drawn as squares, turn them into diamonds
+    let diamond = Transform::from_rotation(Quat::from_rotation_z(FRAC_PI_4))
+        .with_scale(Vec3::splat(FRAC_1_SQRT_2));
+    for (layout, x) in [
+        (SpriteGridLayout::Isometric, -250.0),
+        (
+            SpriteGridLayout::StaggeredIsometric(StaggerIndex::Odd),
+            250.0,
+        ),
+    ] {
+        let mut sprite_grid = SpriteGrid::from_fn(
+            (
+                [10, 10],
+                vec2(40.0, 40.0),
+                SpriteGridAlignment::center(),
+                layout,
+            ),
+            |cell| Some(cell_color(cell)),
+        );
+        for y in 0..sprite_grid.y_len() {
+            for x in 0..sprite_grid.x_len() {
+                sprite_grid.set_cell_transform([x, y], diamond);
+            }
+        }
+        // squash the grid so the diamonds are twice as wide as they are high
+        commands.spawn_bundle(SpriteGridBundle {
+            sprite_grid,
+            transform: Transform::from_xyz(x, 0.0, 0.0).with_scale(vec2(1.0, 0.5).extend(1.0)),
+            ..Default::default()
+        });
+    }
+}
+
+fn highlight(
+    mut hovered: EventReader<CellHovered>,
+    mut exited: EventReader<CellHoverExited>,
+    mut grids: Query<&mut SpriteGrid>,
+) {
+    for event in exited.iter() {
+        if let Ok(mut grid) = grids.get_mut(event.grid) {
+            grid.set(event.cell, cell_color(event.cell));
+        }
+    }
+    for event in hovered.iter() {
+        if let Ok(mut grid) = grids.get_mut(event.grid) {
+            grid.set(event.cell, Color::GOLD);
+        }
+    }
+}
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(SpriteGridPlugin)
+        .add_plugin(SpriteGridPickingPlugin)
+        .add_startup_system(|mut commands: Commands| {
+            commands.spawn_bundle(Camera2dBundle::default());
+        })
+        .add_startup_system(spawn_grids)
+        .add_system(highlight)
+        .run();
+}
@@ -0,0 +1,57 @@
+use bevy::math::vec2;
+use bevy::prelude::*;
+use bevy_sprite_grid::prelude::*;
+
+fn spawn_grid(mut commands: Commands) {
+    let mut sprite_grid = SpriteGrid::from_fn(
+        ([16, 12], vec2(32.0, 32.0), SpriteGridAlignment::center()),
+        |[x, y]| {
+            Some(if (x + y) % 2 == 0 {
+                Color::DARK_GREEN
+            } else {
+                Color::OLIVE
+            })
+        },
+    );
+    // buildings
+    sprite_grid.place([2, 2], [2, 2], Color::MAROON);
+    sprite_grid.place([9, 6], [3, 3], Color::GRAY);
+    sprite_grid.place([12, 1], [2, 4], Color::ORANGE_RED);
+    // a bridge
+    sprite_grid.place([4, 8], [4, 1], Color::BEIGE);
+    commands.spawn_bundle(SpriteGridBundle {
+        sprite_grid,
+        ..Default::default()
+    });
+}
+
+/// Shrinks the hovered cell, or the whole building when hovering any part of one.
+fn highlight(
+    mut hovered: EventReader<CellHovered>,
+    mut exited: EventReader<CellHoverExited>,
+    mut grids: Query<&mut SpriteGrid>,
+) {
+    for event in exited.iter() {
+        if let Ok(mut grid) = grids.get_mut(event.grid) {
+            grid.remove_cell_transform(event.cell);
+        }
+    }
+    for event in hovered.iter() {
+        if let Ok(mut grid) = grids.get_mut(event.grid) {
+            grid.set_cell_transform(event.cell, Transform::from_scale(Vec3::splat(0.8)));
+        }
+    }
+}
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(SpriteGridPlugin)
+        .add_plugin(SpriteGridPickingPlugin)
+        .add_startup_system(|mut commands: Commands| {
+            commands.spawn_bundle(Camera2dBundle::default());
+        })
+        .add_startup_system(spawn_grid)
+        .add_system(highlight)
+        .run();
+}
@@ -196,17 +196,14 @@ fn select_rect(
         grids.for_each_mut(|(transform, mut grid)| {
             let rect = pick_rect(&grid, transform, half_size, camera_transform);
             if let Some(rect) = rect {
-                for x in 0..grid.x_len {
-                    for y in 0..grid.y_len {
-                        grid[[x, y]] = Some(
-                            if rect.xs().contains(&x) && rect.ys().contains(&y) {
-                                [Color::CYAN, Color::AQUAMARINE]
-                            } else {
-                                [Color::ORANGE, Color::ORANGE_RED]
-                            }[(x + y) % 2]
-                                .into(),
-                        )
-                    }
+                let bounds = grid.bounds();
+                for ([x, y], cell) in grid.iter_rect_mut(bounds) {
+                    *cell = if rect.contains([x, y]) {
+                        [Color::CYAN, Color::AQUAMARINE]
+                    } else {
+                        [Color::ORANGE, Color::ORANGE_RED]
+                    }[(x + y) % 2]
+                        .into();
                 }
             }
         });
@@ -0,0 +1,63 @@
+use bevy::math::vec2;
+use bevy::prelude::*;
+use bevy_sprite_grid::prelude::*;
+
+const CELL_SIZE: f32 = 32.0;
+
+fn column_color(x: i64, y: usize) -> Option<SpriteCell> {
+    match (x.rem_euclid(7) as usize + y) % 5 {
+        0 => Some(Color::DARK_GREEN.into()),
+        1 => Some(Color::OLIVE.into()),
+        _ => None,
+    }
+}
+
+fn spawn_grid(mut commands: Commands) {
+    let sprite_grid = SpriteGrid::from_fn(
+        (
+            [30, 20],
+            vec2(CELL_SIZE, CELL_SIZE),
+            SpriteGridAlignment::center(),
+        ),
+        |[x, y]| column_color(x as i64, y),
+    );
+    commands.spawn_bundle(SpriteGridBundle {
+        sprite_grid,
+        ..Default::default()
+    });
+}
+
+fn scroll(
+    time: Res<Time>,
+    mut cameras: Query<&mut Transform, With<Camera2d>>,
+    mut grids: Query<&mut SpriteGrid>,
+) {
+    for mut transform in cameras.iter_mut() {
+        transform.translation.x += 100.0 * time.delta_seconds();
+        let camera_column = (transform.translation.x / CELL_SIZE).floor() as i64;
+        for mut sprite_grid in grids.iter_mut() {
+            // keep the grid's window centered on the camera,
+            // generating a fresh column every time it moves along
+            while sprite_grid.scroll()[0] < camera_column {
+                let x = sprite_grid.scroll()[0] + sprite_grid.x_len() as i64;
+                sprite_grid.shift([1, 0], None);
+                let new_column = sprite_grid.x_len() - 1;
+                for y in 0..sprite_grid.y_len() {
+                    sprite_grid[[new_column, y]] = column_color(x, y);
+                }
+            }
+        }
+    }
+}
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(SpriteGridPlugin)
+        .add_startup_system(|mut commands: Commands| {
+            commands.spawn_bundle(Camera2dBundle::default());
+        })
+        .add_startup_system(spawn_grid)
+        .add_system(scroll)
+        .run();
+}
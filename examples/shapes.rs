@@ -0,0 +1,45 @@
+use bevy::math::vec2;
+use bevy::prelude::*;
+use bevy_sprite_grid::prelude::*;
+
+fn spawn_grid(mut commands: Commands) {
+    let mut sprite_grid =
+        SpriteGrid::empty(([64, 48], vec2(12.0, 12.0), SpriteGridAlignment::center()));
+    sprite_grid.fill_rect(sprite_grid.bounds(), Some(Color::MIDNIGHT_BLUE.into()));
+    sprite_grid.draw_rect(
+        SpriteGridRect {
+            left: 2,
+            right: 30,
+            bottom: 2,
+            top: 20,
+        },
+        Some(Color::WHITE.into()),
+    );
+    sprite_grid.draw_line([4, 4], [27, 17], Some(Color::YELLOW.into()));
+    sprite_grid.fill_circle([46, 32], 10, Some(Color::ORANGE.into()));
+    sprite_grid.draw_circle([46, 32], 13, Some(Color::RED.into()));
+    sprite_grid.fill_polygon(
+        &[
+            vec2(4.0, 26.0),
+            vec2(28.0, 30.0),
+            vec2(20.0, 45.0),
+            vec2(8.0, 40.0),
+        ],
+        Some(Color::GREEN.into()),
+    );
+    commands.spawn_bundle(SpriteGridBundle {
+        sprite_grid,
+        ..Default::default()
+    });
+}
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(SpriteGridPlugin)
+        .add_startup_system(|mut commands: Commands| {
+            commands.spawn_bundle(Camera2dBundle::default());
+        })
+        .add_startup_system(spawn_grid)
+        .run();
+}
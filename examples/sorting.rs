@@ -0,0 +1,43 @@
+use bevy::math::vec2;
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use bevy_sprite_grid::prelude::*;
+
+/// Sprites three cells tall, sorted so the sprites lower down the grid
+/// are drawn over the ones above them.
+fn spawn_grid(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let cell_size = vec2(32.0, 32.0);
+    let mut sprite_grid = SpriteGrid::from_fn(
+        ([12, 12], cell_size, SpriteGridAlignment::center()),
+        |[x, y]| {
+            ((x * 5 + y * 3) % 4 == 0).then(|| TexturedCell {
+                texture: asset_server.load("sprite.png").into(),
+                color: Color::hsl(30.0 * x as f32, 0.6, 0.6),
+                custom_size: Some(vec2(1.5, 3.0) * cell_size),
+                // stand the sprites on their cells
+                anchor: Anchor::BottomCenter,
+                ..Default::default()
+            })
+        },
+    );
+    sprite_grid.sorting = SpriteGridSorting::ByRow;
+    // the sprites reach further above their cells than the default margin covers
+    sprite_grid.culling = SpriteGridCulling::Enabled {
+        margin: vec2(1.5, 3.0) * cell_size,
+    };
+    commands.spawn_bundle(SpriteGridBundle {
+        sprite_grid,
+        ..Default::default()
+    });
+}
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(SpriteGridPlugin)
+        .add_startup_system(|mut commands: Commands| {
+            commands.spawn_bundle(Camera2dBundle::default());
+        })
+        .add_startup_system(spawn_grid)
+        .run();
+}
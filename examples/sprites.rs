@@ -28,6 +28,7 @@ fn spawn_grid(mut commands: Commands, asset_server: Res<AssetServer>) {
                 flip_x: false,
                 flip_y: (x + y) % 2 == 0,
                 custom_size: Some(cell_size),
+                ..Default::default()
             }
             .into()
         });
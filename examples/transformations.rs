@@ -19,6 +19,7 @@ fn spawn_grid(mut commands: Commands, assets: Res<AssetServer>) {
                     flip_x: false,
                     flip_y: (x + y) % 2 == 0,
                     custom_size: Some(cell_size),
+                    ..Default::default()
                 }
                 .into()
             },
@@ -0,0 +1,279 @@
+use crate::prelude::*;
+use bevy::input::InputSystem;
+use bevy::math::vec2;
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::render::camera::Viewport;
+use bevy::render::view::RenderLayers;
+
+/// The cursor moved onto a grid cell.
+#[derive(Copy, Clone, Debug)]
+pub struct CellHovered {
+    pub grid: Entity,
+    pub cell: [usize; 2],
+}
+
+/// The cursor moved off a grid cell.
+#[derive(Copy, Clone, Debug)]
+pub struct CellHoverExited {
+    pub grid: Entity,
+    pub cell: [usize; 2],
+}
+
+/// A mouse button was pressed over a grid cell.
+#[derive(Copy, Clone, Debug)]
+pub struct CellClicked {
+    pub grid: Entity,
+    pub cell: [usize; 2],
+    pub button: MouseButton,
+}
+
+/// The cursor moved onto another cell of the grid a mouse button
+/// was pressed on, while the button is still held.
+#[derive(Copy, Clone, Debug)]
+pub struct CellDragged {
+    pub grid: Entity,
+    /// the cell the drag started from
+    pub start: [usize; 2],
+    /// the cell the cursor moved off
+    pub from: [usize; 2],
+    /// the cell the cursor moved onto
+    pub cell: [usize; 2],
+    pub button: MouseButton,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct CellDrag {
+    grid: Entity,
+    start: [usize; 2],
+    button: MouseButton,
+}
+
+/// Where the cursor is, updated by the [`SpriteGridPickingPlugin`].
+#[derive(Clone, Debug, Default)]
+pub struct SpriteGridCursor {
+    /// World position of the cursor, `None` if it isn't over any camera's viewport.
+    pub position: Option<Vec2>,
+    /// The topmost grid under the cursor and the cell of that grid the cursor is over.
+    pub hovered: Option<(Entity, [usize; 2])>,
+    /// Grids whose cell under the cursor isn't accepted by the filter
+    /// are ignored, so the grids below them can be picked.
+    pub filter: SpriteGridPickFilter,
+    drag: Option<CellDrag>,
+}
+
+/// World position of the cursor seen through the camera,
+/// `None` if the cursor isn't over the camera's viewport.
+pub fn cursor_world_position(
+    windows: &Windows,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<Vec2> {
+    let window = match camera.target {
+        RenderTarget::Window(id) => windows.get(id)?,
+        _ => return None,
+    };
+    viewport_to_world(
+        window.cursor_position()?,
+        vec2(window.width(), window.height()),
+        window.scale_factor() as f32,
+        camera.viewport.as_ref(),
+        camera_transform,
+        camera.projection_matrix(),
+    )
+}
+
+/// World position of `cursor`, in logical pixels from the bottom left of a window
+/// `window_size` logical pixels large, seen through a camera with the given viewport,
+/// transform and projection. `None` if `cursor` isn't inside the viewport.
+fn viewport_to_world(
+    cursor: Vec2,
+    window_size: Vec2,
+    scale_factor: f32,
+    viewport: Option<&Viewport>,
+    camera_transform: &GlobalTransform,
+    projection: Mat4,
+) -> Option<Vec2> {
+    let (viewport_min, viewport_size) = match viewport {
+        Some(viewport) => {
+            let position = viewport.physical_position.as_vec2() / scale_factor;
+            let size = viewport.physical_size.as_vec2() / scale_factor;
+            // viewports are positioned from the top left of the window
+            (vec2(position.x, window_size.y - position.y - size.y), size)
+        }
+        None => (Vec2::ZERO, window_size),
+    };
+    let ndc = 2.0 * (cursor - viewport_min) / viewport_size - Vec2::ONE;
+    if 1.0 < ndc.abs().max_element() {
+        return None;
+    }
+    let ndc_to_world = camera_transform.compute_matrix() * projection.inverse();
+    Some(ndc_to_world.project_point3(ndc.extend(0.0)).truncate())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn pick_cursor_cells(
+    windows: Res<Windows>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    cameras: Query<(&Camera, &GlobalTransform, Option<&RenderLayers>), With<Camera2d>>,
+    grids: Query<(
+        Entity,
+        &SpriteGrid,
+        &GlobalTransform,
+        &ComputedVisibility,
+        Option<&RenderLayers>,
+    )>,
+    mut cursor: ResMut<SpriteGridCursor>,
+    mut hovered_events: EventWriter<CellHovered>,
+    mut exited_events: EventWriter<CellHoverExited>,
+    mut clicked_events: EventWriter<CellClicked>,
+    mut dragged_events: EventWriter<CellDragged>,
+) {
+    let topmost = cameras
+        .iter()
+        .filter(|(camera, ..)| camera.is_active)
+        .filter_map(|(camera, transform, layers)| {
+            cursor_world_position(&windows, camera, transform)
+                .map(|position| (camera.priority, position, layers))
+        })
+        .max_by_key(|&(priority, ..)| priority);
+    cursor.position = topmost.map(|(_, position, _)| position);
+
+    let hovered = topmost.and_then(|(_, position, camera_layers)| {
+        let camera_layers = camera_layers.copied().unwrap_or_default();
+        // only grids the camera can see are picked
+        let visible_grids = grids
+            .iter()
+            .filter(|(.., visibility, _)| visibility.is_visible())
+            .filter(|(.., layers)| camera_layers.intersects(&layers.copied().unwrap_or_default()))
+            .map(|(entity, grid, transform, ..)| (entity, grid, transform));
+        pick_all(visible_grids, position, cursor.filter)
+            .first()
+            .copied()
+    });
+
+    if hovered != cursor.hovered {
+        if let Some((grid, cell)) = cursor.hovered {
+            exited_events.send(CellHoverExited { grid, cell });
+        }
+        if let Some((grid, cell)) = hovered {
+            hovered_events.send(CellHovered { grid, cell });
+            if let (Some(drag), Some((previous_grid, from))) = (cursor.drag, cursor.hovered) {
+                if drag.grid == grid && previous_grid == grid {
+                    dragged_events.send(CellDragged {
+                        grid,
+                        start: drag.start,
+                        from,
+                        cell,
+                        button: drag.button,
+                    });
+                }
+            }
+        }
+        cursor.hovered = hovered;
+    }
+
+    if let Some(drag) = cursor.drag {
+        if !mouse_buttons.pressed(drag.button) {
+            cursor.drag = None;
+        }
+    }
+    if let Some((grid, cell)) = hovered {
+        for &button in mouse_buttons.get_just_pressed() {
+            clicked_events.send(CellClicked { grid, cell, button });
+            cursor.drag = Some(CellDrag {
+                grid,
+                start: cell,
+                button,
+            });
+        }
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemLabel)]
+pub enum SpriteGridPickingSystem {
+    PickCursorCells,
+}
+
+/// Tracks which grid cell is under the cursor and sends
+/// [`CellHovered`], [`CellHoverExited`], [`CellClicked`] and [`CellDragged`] events.
+///
+/// Not added by the [`SpriteGridPlugin`].
+pub struct SpriteGridPickingPlugin;
+
+impl Plugin for SpriteGridPickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpriteGridCursor>()
+            .add_event::<CellHovered>()
+            .add_event::<CellHoverExited>()
+            .add_event::<CellClicked>()
+            .add_event::<CellDragged>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                pick_cursor_cells
+                    .label(SpriteGridPickingSystem::PickCursorCells)
+                    .after(InputSystem),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_positions_are_relative_to_the_viewport() {
+        // a 400x300 viewport 100 logical pixels from the left and 50 from the top
+        // of an 800x600 window, at a scale factor of 2
+        let window_size = vec2(800.0, 600.0);
+        let viewport = Viewport {
+            physical_position: UVec2::new(200, 100),
+            physical_size: UVec2::new(800, 600),
+            ..Default::default()
+        };
+        let projection = Mat4::orthographic_rh(-200.0, 200.0, -150.0, 150.0, 0.0, 1000.0);
+        let camera_transform = GlobalTransform::from(
+            Transform::from_xyz(10.0, 20.0, 999.0).with_scale(Vec3::splat(2.0)),
+        );
+        let world = |cursor: Vec2| {
+            viewport_to_world(
+                cursor,
+                window_size,
+                2.0,
+                Some(&viewport),
+                &camera_transform,
+                projection,
+            )
+        };
+        // the viewport spans 100..500 by 250..550 from the bottom left of the window
+        for (cursor, expected) in [
+            (vec2(300.0, 400.0), vec2(10.0, 20.0)),
+            (vec2(100.0, 250.0), vec2(-390.0, -280.0)),
+            (vec2(500.0, 550.0), vec2(410.0, 320.0)),
+            (vec2(350.0, 300.0), vec2(110.0, -180.0)),
+        ] {
+            let position = world(cursor).unwrap();
+            assert!(position.distance(expected) < 1e-3, "{cursor} {position}");
+        }
+        for outside in [
+            vec2(99.0, 400.0),
+            vec2(501.0, 400.0),
+            vec2(300.0, 249.0),
+            vec2(300.0, 551.0),
+        ] {
+            assert_eq!(world(outside), None);
+        }
+
+        // without a viewport the camera covers the whole window
+        let position = viewport_to_world(
+            vec2(800.0, 600.0),
+            window_size,
+            2.0,
+            None,
+            &camera_transform,
+            projection,
+        );
+        // the projection still maps 400x300 world units onto the window
+        assert!(position.unwrap().distance(vec2(410.0, 320.0)) < 1e-3);
+    }
+}
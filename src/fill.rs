@@ -0,0 +1,147 @@
+use crate::prelude::*;
+use bevy::utils::HashSet;
+
+/// Which cells count as connected when selecting and filling regions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpriteGridConnectivity {
+    /// Cells are connected to the four cells they share an edge with.
+    Four,
+    /// Cells are connected to the eight cells they share an edge or a corner with.
+    Eight,
+}
+
+impl Default for SpriteGridConnectivity {
+    fn default() -> Self {
+        Self::Four
+    }
+}
+
+impl SpriteGridConnectivity {
+    pub fn offsets(self) -> &'static [[i64; 2]] {
+        match self {
+            Self::Four => &NEIGHBORS_4,
+            Self::Eight => &NEIGHBORS_8,
+        }
+    }
+}
+
+impl SpriteGrid {
+    /// The cells connected to `start` through cells that satisfy `predicate`,
+    /// including `start` itself. Empty if `start` doesn't satisfy `predicate`.
+    ///
+    /// The cells are returned in breadth first order starting from `start`.
+    pub fn select_connected(
+        &self,
+        start: [usize; 2],
+        mut predicate: impl FnMut([usize; 2], &Option<SpriteCell>) -> bool,
+        connectivity: SpriteGridConnectivity,
+    ) -> Vec<[usize; 2]> {
+        if !predicate(start, &self[start]) {
+            return Vec::new();
+        }
+        let mut visited = HashSet::default();
+        visited.insert(start);
+        let mut selected = vec![start];
+        let mut next = 0;
+        while let Some(&cell) = selected.get(next) {
+            next += 1;
+            for &offset in connectivity.offsets() {
+                if let Some(neighbor) = self.offset(cell, offset) {
+                    if visited.insert(neighbor) && predicate(neighbor, &self[neighbor]) {
+                        selected.push(neighbor);
+                    }
+                }
+            }
+        }
+        selected
+    }
+
+    /// Sets every cell selected by [`SpriteGrid::select_connected`] to `replacement`,
+    /// and returns the filled cells.
+    pub fn flood_fill(
+        &mut self,
+        start: [usize; 2],
+        predicate: impl FnMut([usize; 2], &Option<SpriteCell>) -> bool,
+        replacement: Option<SpriteCell>,
+        connectivity: SpriteGridConnectivity,
+    ) -> Vec<[usize; 2]> {
+        let selected = self.select_connected(start, predicate, connectivity);
+        for &cell in &selected {
+            self.put(cell, replacement.clone());
+        }
+        selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::prelude::*;
+
+    /// A grid with a diagonal wall cutting off its bottom left corner.
+    fn walled() -> SpriteGrid {
+        SpriteGrid::from_fn(([6, 6], Vec2::ONE), |[x, y]| {
+            (x + y == 3).then_some(SpriteCell::Color(Color::WHITE))
+        })
+    }
+
+    #[test]
+    fn four_way_fills_stop_at_diagonal_walls() {
+        let mut grid = walled();
+        let filled = grid.flood_fill(
+            [0, 0],
+            |_, cell| cell.is_none(),
+            Some(SpriteCell::Color(Color::RED)),
+            SpriteGridConnectivity::Four,
+        );
+        assert_eq!(filled.len(), 6);
+        assert_eq!(filled[0], [0, 0]);
+        for y in 0..6 {
+            for x in 0..6 {
+                let expected = match x + y {
+                    0..=2 => Some(Color::RED),
+                    3 => Some(Color::WHITE),
+                    _ => None,
+                };
+                let color = match &grid[[x, y]] {
+                    Some(SpriteCell::Color(color)) => Some(*color),
+                    _ => None,
+                };
+                assert_eq!(color, expected, "{x} {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn eight_way_fills_leak_through_diagonal_walls() {
+        let mut grid = walled();
+        let filled = grid.flood_fill(
+            [0, 0],
+            |_, cell| cell.is_none(),
+            Some(SpriteCell::Color(Color::RED)),
+            SpriteGridConnectivity::Eight,
+        );
+        assert_eq!(filled.len(), 36 - 4);
+        assert_eq!(grid.iter(0..6, 0..6).count(), 36);
+
+        // nothing is selected from a cell that doesn't match
+        let wall = grid.select_connected(
+            [3, 0],
+            |_, cell| cell.is_none(),
+            SpriteGridConnectivity::Eight,
+        );
+        assert!(wall.is_empty());
+        let wall = walled().select_connected(
+            [3, 0],
+            |_, cell| cell.is_some(),
+            SpriteGridConnectivity::Eight,
+        );
+        assert_eq!(wall, vec![[3, 0], [2, 1], [1, 2], [0, 3]]);
+        let wall = walled().select_connected(
+            [3, 0],
+            |_, cell| cell.is_some(),
+            SpriteGridConnectivity::Four,
+        );
+        assert_eq!(wall, vec![[3, 0]]);
+    }
+}
@@ -0,0 +1,381 @@
+use bevy::math::vec2;
+use bevy::prelude::*;
+
+/// Which way up the hexagons of a hex grid are.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HexOrientation {
+    /// Hexagons with a vertex at the top, laid out in horizontal rows.
+    PointyTop,
+    /// Hexagons with an edge at the top, laid out in vertical columns.
+    FlatTop,
+}
+
+/// How the cells of a hex grid are indexed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HexCoordinates {
+    /// Offset coordinates where odd rows (pointy top)
+    /// or odd columns (flat top) are shoved along by half a cell.
+    OddOffset,
+    /// Offset coordinates where even rows (pointy top)
+    /// or even columns (flat top) are shoved along by half a cell.
+    EvenOffset,
+    /// Axial `[q, r]` coordinates, the grid is rhombus shaped.
+    Axial,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HexLayout {
+    pub orientation: HexOrientation,
+    pub coordinates: HexCoordinates,
+}
+
+/// Axial offsets of the six neighbors of a hex cell.
+pub const HEX_DIRECTIONS: [[i64; 2]; 6] = [[1, 0], [1, -1], [0, -1], [-1, 0], [-1, 1], [0, 1]];
+
+impl HexLayout {
+    pub fn pointy_top(coordinates: HexCoordinates) -> Self {
+        Self {
+            orientation: HexOrientation::PointyTop,
+            coordinates,
+        }
+    }
+
+    pub fn flat_top(coordinates: HexCoordinates) -> Self {
+        Self {
+            orientation: HexOrientation::FlatTop,
+            coordinates,
+        }
+    }
+
+    /// Converts cell coordinates in this layout to axial `[q, r]` coordinates.
+    pub fn offset_to_axial(self, [x, y]: [i64; 2]) -> [i64; 2] {
+        match (self.orientation, self.coordinates) {
+            (_, HexCoordinates::Axial) => [x, y],
+            (HexOrientation::PointyTop, HexCoordinates::OddOffset) => [x - (y - (y & 1)) / 2, y],
+            (HexOrientation::PointyTop, HexCoordinates::EvenOffset) => [x - (y + (y & 1)) / 2, y],
+            (HexOrientation::FlatTop, HexCoordinates::OddOffset) => [x, y - (x - (x & 1)) / 2],
+            (HexOrientation::FlatTop, HexCoordinates::EvenOffset) => [x, y - (x + (x & 1)) / 2],
+        }
+    }
+
+    /// Converts axial `[q, r]` coordinates to cell coordinates in this layout.
+    pub fn axial_to_offset(self, [q, r]: [i64; 2]) -> [i64; 2] {
+        match (self.orientation, self.coordinates) {
+            (_, HexCoordinates::Axial) => [q, r],
+            (HexOrientation::PointyTop, HexCoordinates::OddOffset) => [q + (r - (r & 1)) / 2, r],
+            (HexOrientation::PointyTop, HexCoordinates::EvenOffset) => [q + (r + (r & 1)) / 2, r],
+            (HexOrientation::FlatTop, HexCoordinates::OddOffset) => [q, r + (q - (q & 1)) / 2],
+            (HexOrientation::FlatTop, HexCoordinates::EvenOffset) => [q, r + (q + (q & 1)) / 2],
+        }
+    }
+
+    /// Offset of the axial lattice along the staggered axis, in cells.
+    /// Even offset layouts shove their first row or column along by half a cell.
+    fn lattice_shift(self) -> f32 {
+        match self.coordinates {
+            HexCoordinates::EvenOffset => 0.5,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Rounds fractional axial coordinates to the nearest hex.
+fn hex_round(q: f32, r: f32) -> [i64; 2] {
+    let s = -q - r;
+    let mut rq = q.round();
+    let mut rr = r.round();
+    let rs = s.round();
+    let dq = (rq - q).abs();
+    let dr = (rr - r).abs();
+    let ds = (rs - s).abs();
+    if dr < dq && ds < dq {
+        rq = -rr - rs;
+    } else if ds < dr {
+        rr = -rq - rs;
+    }
+    [rq as i64, rr as i64]
+}
+
+/// Which rows of a staggered isometric grid are shoved along by half a cell.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StaggerIndex {
+    Odd,
+    Even,
+}
+
+/// How cells are positioned within a SpriteGrid.
+///
+/// Positions are in the grid's local space before alignment,
+/// with the bottom left corner of the grid's bounding box at the origin.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpriteGridLayout {
+    /// Rectangular cells in rows and columns.
+    Rectangular,
+    /// Hexagonal cells. `cell_size` is the size of each hexagon's bounding box.
+    Hex(HexLayout),
+    /// Diamond shaped cells forming a diamond shaped grid. The x axis runs up
+    /// and to the right, the y axis up and to the left, so the cell `[0, 0]`
+    /// is at the bottom. `cell_size` is the size of each diamond's bounding box.
+    Isometric,
+    /// Diamond shaped cells in rows half a cell high, forming a rectangular grid.
+    /// `cell_size` is the size of each diamond's bounding box.
+    StaggeredIsometric(StaggerIndex),
+}
+
+impl Default for SpriteGridLayout {
+    fn default() -> Self {
+        Self::Rectangular
+    }
+}
+
+impl SpriteGridLayout {
+    /// Size of the bounding box of a grid of `[x_len, y_len]` cells.
+    pub fn size(self, [x_len, y_len]: [usize; 2], cell_size: Vec2) -> Vec2 {
+        if x_len == 0 || y_len == 0 {
+            return Vec2::ZERO;
+        }
+        match self {
+            Self::Rectangular => vec2(x_len as f32, y_len as f32) * cell_size,
+            Self::Hex(hex) => {
+                // hexes line up along one axis and are staggered along the other
+                let (along, across) = match hex.orientation {
+                    HexOrientation::PointyTop => (x_len as f32, y_len as f32),
+                    HexOrientation::FlatTop => (y_len as f32, x_len as f32),
+                };
+                let stagger = match hex.coordinates {
+                    HexCoordinates::Axial => 0.5 * (across - 1.0),
+                    HexCoordinates::EvenOffset => 0.5,
+                    HexCoordinates::OddOffset if 1.0 < across => 0.5,
+                    HexCoordinates::OddOffset => 0.0,
+                };
+                let along = along + stagger;
+                let across = 0.75 * (across - 1.0) + 1.0;
+                match hex.orientation {
+                    HexOrientation::PointyTop => vec2(along, across) * cell_size,
+                    HexOrientation::FlatTop => vec2(across, along) * cell_size,
+                }
+            }
+            Self::Isometric => 0.5 * (x_len + y_len) as f32 * cell_size,
+            Self::StaggeredIsometric(index) => {
+                let stagger = if index == StaggerIndex::Even || 1 < y_len {
+                    0.5
+                } else {
+                    0.0
+                };
+                vec2(x_len as f32 + stagger, 0.5 * (y_len + 1) as f32) * cell_size
+            }
+        }
+    }
+
+    /// Center of the cell `[x, y]` of a grid of `[x_len, y_len]` cells.
+    /// The coordinates aren't bounds checked.
+    pub fn center(self, [x, y]: [i64; 2], [_, y_len]: [usize; 2], cell_size: Vec2) -> Vec2 {
+        let half = 0.5 * cell_size;
+        match self {
+            Self::Rectangular => vec2(x as f32, y as f32) * cell_size + half,
+            Self::Hex(hex) => {
+                let [q, r] = hex.offset_to_axial([x, y]);
+                let (q, r) = (q as f32, r as f32);
+                let shift = hex.lattice_shift();
+                match hex.orientation {
+                    HexOrientation::PointyTop => {
+                        vec2(q + 0.5 * r + shift, 0.75 * r) * cell_size + half
+                    }
+                    HexOrientation::FlatTop => {
+                        vec2(0.75 * q, r + 0.5 * q + shift) * cell_size + half
+                    }
+                }
+            }
+            Self::Isometric => vec2((x - y + y_len as i64) as f32, (x + y + 1) as f32) * half,
+            Self::StaggeredIsometric(index) => {
+                let shoved = match index {
+                    StaggerIndex::Odd => y & 1,
+                    StaggerIndex::Even => (y + 1) & 1,
+                };
+                vec2(x as f32 + 0.5 * shoved as f32, 0.5 * y as f32) * cell_size + half
+            }
+        }
+    }
+
+    /// The cell containing `point` in a grid of `[x_len, y_len]` cells.
+    /// The result isn't bounds checked.
+    pub fn cell_at(self, point: Vec2, [_, y_len]: [usize; 2], cell_size: Vec2) -> [i64; 2] {
+        match self {
+            Self::Rectangular => {
+                let cell = (point / cell_size).floor();
+                [cell.x as i64, cell.y as i64]
+            }
+            Self::Hex(hex) => {
+                let p = (point - 0.5 * cell_size) / cell_size;
+                let shift = hex.lattice_shift();
+                let axial = match hex.orientation {
+                    HexOrientation::PointyTop => {
+                        let r = p.y / 0.75;
+                        hex_round(p.x - shift - 0.5 * r, r)
+                    }
+                    HexOrientation::FlatTop => {
+                        let q = p.x / 0.75;
+                        hex_round(q, p.y - shift - 0.5 * q)
+                    }
+                };
+                hex.axial_to_offset(axial)
+            }
+            Self::Isometric => {
+                // u = x - y and v = x + y at the cell centers
+                let u = 2.0 * point.x / cell_size.x - y_len as f32;
+                let v = 2.0 * point.y / cell_size.y - 1.0;
+                diamond_at(u, v)
+            }
+            Self::StaggeredIsometric(index) => {
+                // the rows interleave into a diamond lattice, with
+                // u = 2x + shoved - shift and v = y at the cell centers
+                let shift = match index {
+                    StaggerIndex::Odd => 0.0,
+                    StaggerIndex::Even => 1.0,
+                };
+                let u = 2.0 * point.x / cell_size.x - 1.0 - shift;
+                let v = 2.0 * point.y / cell_size.y - 1.0;
+                let [a, b] = diamond_at(u, v);
+                let y = a + b;
+                let x = match index {
+                    StaggerIndex::Odd => (a - b - (y & 1)) / 2,
+                    StaggerIndex::Even => (a - b + (y & 1)) / 2,
+                };
+                [x, y]
+            }
+        }
+    }
+
+    /// The vertices of a cell relative to its center, counter-clockwise.
+    pub fn corners(self, cell_size: Vec2) -> Vec<Vec2> {
+        let [w, h] = (0.5 * cell_size).to_array();
+        match self {
+            Self::Rectangular => vec![vec2(-w, -h), vec2(w, -h), vec2(w, h), vec2(-w, h)],
+            Self::Hex(HexLayout {
+                orientation: HexOrientation::PointyTop,
+                ..
+            }) => vec![
+                vec2(0.0, h),
+                vec2(-w, 0.5 * h),
+                vec2(-w, -0.5 * h),
+                vec2(0.0, -h),
+                vec2(w, -0.5 * h),
+                vec2(w, 0.5 * h),
+            ],
+            Self::Hex(HexLayout {
+                orientation: HexOrientation::FlatTop,
+                ..
+            }) => vec![
+                vec2(w, 0.0),
+                vec2(0.5 * w, h),
+                vec2(-0.5 * w, h),
+                vec2(-w, 0.0),
+                vec2(-0.5 * w, -h),
+                vec2(0.5 * w, -h),
+            ],
+            Self::Isometric | Self::StaggeredIsometric(_) => {
+                vec![vec2(0.0, -h), vec2(w, 0.0), vec2(0.0, h), vec2(-w, 0.0)]
+            }
+        }
+    }
+
+    /// Z offset in `[0, 1)` that draws the cell `[x, y]` of a grid of `[x_len, y_len]`
+    /// cells over the cells behind it.
+    ///
+    /// Isometric cells further down the screen are in front, other layouts don't overlap
+    /// and are all at zero.
+    pub fn depth(self, [x, y]: [usize; 2], [x_len, y_len]: [usize; 2]) -> f32 {
+        match self {
+            Self::Isometric => 1.0 - (x + y + 1) as f32 / (x_len + y_len) as f32,
+            Self::StaggeredIsometric(_) => 1.0 - (y + 1) as f32 / y_len as f32,
+            _ => 0.0,
+        }
+    }
+}
+
+/// The diamond containing the point `(u, v)`, where the diamond `[a, b]`
+/// is centered on `u = a - b`, `v = a + b`.
+fn diamond_at(u: f32, v: f32) -> [i64; 2] {
+    let a = (0.5 * (u + v) + 0.5).floor() as i64;
+    let b = (0.5 * (v - u) + 0.5).floor() as i64;
+    [a, b]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: [usize; 2] = [7, 6];
+
+    /// Checks that the center of each cell, and points around it,
+    /// are inside that cell.
+    fn assert_round_trips(layout: SpriteGridLayout, nudges: &[Vec2]) {
+        let cell_size = vec2(40.0, 46.0);
+        for y in -2..8 {
+            for x in -2..9 {
+                let center = layout.center([x, y], SIZE, cell_size);
+                for &nudge in nudges {
+                    let point = center + nudge * cell_size;
+                    assert_eq!(
+                        layout.cell_at(point, SIZE, cell_size),
+                        [x, y],
+                        "{layout:?} at {point}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hex_centers_round_trip() {
+        // inside the circle inscribed in each hexagon
+        let nudges = [
+            Vec2::ZERO,
+            vec2(0.3, 0.0),
+            vec2(-0.3, 0.0),
+            vec2(0.0, 0.3),
+            vec2(0.0, -0.3),
+            vec2(0.2, 0.2),
+            vec2(-0.2, -0.2),
+        ];
+        for orientation in [HexOrientation::PointyTop, HexOrientation::FlatTop] {
+            for coordinates in [
+                HexCoordinates::OddOffset,
+                HexCoordinates::EvenOffset,
+                HexCoordinates::Axial,
+            ] {
+                let hex = HexLayout {
+                    orientation,
+                    coordinates,
+                };
+                assert_round_trips(SpriteGridLayout::Hex(hex), &nudges);
+                for y in -3..4 {
+                    for x in -3..4 {
+                        assert_eq!(hex.axial_to_offset(hex.offset_to_axial([x, y])), [x, y]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn isometric_centers_round_trip() {
+        // inside the diamond of each cell
+        let nudges = [
+            Vec2::ZERO,
+            vec2(0.3, 0.0),
+            vec2(-0.3, 0.0),
+            vec2(0.0, 0.3),
+            vec2(0.0, -0.3),
+            vec2(0.15, 0.15),
+            vec2(-0.15, -0.15),
+        ];
+        for layout in [
+            SpriteGridLayout::Isometric,
+            SpriteGridLayout::StaggeredIsometric(StaggerIndex::Odd),
+            SpriteGridLayout::StaggeredIsometric(StaggerIndex::Even),
+        ] {
+            assert_round_trips(layout, &nudges);
+        }
+    }
+}
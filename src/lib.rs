@@ -1,15 +1,29 @@
 pub mod bundles;
+pub mod cursor;
+pub mod fill;
+pub mod layout;
+pub mod neighbors;
 pub mod picking;
+pub mod region;
 pub mod render;
+pub mod shapes;
 pub mod sprite_cell;
 pub mod sprite_grid;
+pub mod storage;
 pub mod view;
 
 pub mod prelude {
     pub use crate::bundles::*;
+    pub use crate::cursor::*;
+    pub use crate::fill::*;
+    pub use crate::layout::*;
+    pub use crate::neighbors::*;
     pub use crate::picking::*;
+    pub use crate::region::*;
     pub use crate::sprite_cell::*;
     pub use crate::sprite_grid::*;
+    pub use crate::storage::SpriteGridStorage;
+    pub use crate::storage::CHUNK_SIZE;
     pub use crate::view::*;
     pub use crate::SpriteGridPlugin;
 }
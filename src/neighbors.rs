@@ -0,0 +1,242 @@
+use crate::prelude::*;
+
+/// Offsets of the four cells sharing an edge with a cell:
+/// left, right, below and above.
+pub const NEIGHBORS_4: [[i64; 2]; 4] = [[-1, 0], [1, 0], [0, -1], [0, 1]];
+
+/// Offsets of the eight cells sharing an edge or a corner with a cell,
+/// in row-major order from the bottom left.
+pub const NEIGHBORS_8: [[i64; 2]; 8] = [
+    [-1, -1],
+    [0, -1],
+    [1, -1],
+    [-1, 0],
+    [1, 0],
+    [-1, 1],
+    [0, 1],
+    [1, 1],
+];
+
+fn square_offsets(radius: usize) -> impl Iterator<Item = [i64; 2]> {
+    let r = radius as i64;
+    (-r..=r)
+        .flat_map(move |dy| (-r..=r).map(move |dx| [dx, dy]))
+        .filter(|&offset| offset != [0, 0])
+}
+
+impl SpriteGrid {
+    /// The cell `[dx, dy]` away from `[x, y]`, or `None` if it is outside of the grid.
+    pub fn offset(&self, [x, y]: [usize; 2], [dx, dy]: [i64; 2]) -> Option<[usize; 2]> {
+        let x = x as i64 + dx;
+        let y = y as i64 + dy;
+        if 0 <= x && x < self.x_len() as i64 && 0 <= y && y < self.y_len() as i64 {
+            Some([x as usize, y as usize])
+        } else {
+            None
+        }
+    }
+
+    /// The cell `[dx, dy]` away from `[x, y]`, wrapping around the edges of the grid.
+    pub fn offset_wrapping(&self, [x, y]: [usize; 2], [dx, dy]: [i64; 2]) -> [usize; 2] {
+        [
+            (x as i64 + dx).rem_euclid(self.x_len() as i64) as usize,
+            (y as i64 + dy).rem_euclid(self.y_len() as i64) as usize,
+        ]
+    }
+
+    fn check_neighborhood(&self, cell: [usize; 2]) {
+        assert!(
+            self.bounds().contains(cell),
+            "cell {cell:?} is outside of the sprite grid"
+        );
+    }
+
+    fn neighbors_at(
+        &self,
+        cell: [usize; 2],
+        offsets: impl Iterator<Item = [i64; 2]>,
+    ) -> impl Iterator<Item = ([usize; 2], &Option<SpriteCell>)> {
+        self.check_neighborhood(cell);
+        offsets
+            .filter_map(move |offset| self.offset(cell, offset))
+            .map(move |neighbor| (neighbor, &self[neighbor]))
+    }
+
+    fn neighbors_at_wrapping(
+        &self,
+        cell: [usize; 2],
+        offsets: impl Iterator<Item = [i64; 2]>,
+    ) -> impl Iterator<Item = ([usize; 2], &Option<SpriteCell>)> {
+        self.check_neighborhood(cell);
+        offsets
+            .map(move |offset| self.offset_wrapping(cell, offset))
+            .map(move |neighbor| (neighbor, &self[neighbor]))
+    }
+
+    /// The cells sharing an edge with `cell` that are inside the grid,
+    /// in the order of [`NEIGHBORS_4`].
+    pub fn neighbors4(
+        &self,
+        cell: [usize; 2],
+    ) -> impl Iterator<Item = ([usize; 2], &Option<SpriteCell>)> {
+        self.neighbors_at(cell, NEIGHBORS_4.into_iter())
+    }
+
+    /// The cells sharing an edge or a corner with `cell` that are inside the grid,
+    /// in the order of [`NEIGHBORS_8`].
+    pub fn neighbors8(
+        &self,
+        cell: [usize; 2],
+    ) -> impl Iterator<Item = ([usize; 2], &Option<SpriteCell>)> {
+        self.neighbors_at(cell, NEIGHBORS_8.into_iter())
+    }
+
+    /// The cells inside the grid at most `radius` cells away from `cell`
+    /// horizontally and vertically, not including `cell` itself.
+    pub fn neighbors_within(
+        &self,
+        cell: [usize; 2],
+        radius: usize,
+    ) -> impl Iterator<Item = ([usize; 2], &Option<SpriteCell>)> {
+        self.neighbors_at(cell, square_offsets(radius))
+    }
+
+    /// Like [`SpriteGrid::neighbors4`] but wrapping around the edges of the grid.
+    /// On grids narrower than three cells a neighbor can be visited more than once.
+    pub fn neighbors4_wrapping(
+        &self,
+        cell: [usize; 2],
+    ) -> impl Iterator<Item = ([usize; 2], &Option<SpriteCell>)> {
+        self.neighbors_at_wrapping(cell, NEIGHBORS_4.into_iter())
+    }
+
+    /// Like [`SpriteGrid::neighbors8`] but wrapping around the edges of the grid.
+    /// On grids narrower than three cells a neighbor can be visited more than once.
+    pub fn neighbors8_wrapping(
+        &self,
+        cell: [usize; 2],
+    ) -> impl Iterator<Item = ([usize; 2], &Option<SpriteCell>)> {
+        self.neighbors_at_wrapping(cell, NEIGHBORS_8.into_iter())
+    }
+
+    /// Like [`SpriteGrid::neighbors_within`] but wrapping around the edges of the grid.
+    /// If the neighborhood is wider than the grid a neighbor can be visited more than once.
+    pub fn neighbors_within_wrapping(
+        &self,
+        cell: [usize; 2],
+        radius: usize,
+    ) -> impl Iterator<Item = ([usize; 2], &Option<SpriteCell>)> {
+        self.neighbors_at_wrapping(cell, square_offsets(radius))
+    }
+
+    /// The cells sharing an edge with `cell` on a hex grid that are inside the grid,
+    /// in the order of [`HEX_DIRECTIONS`]. Empty if the grid's layout isn't hexagonal.
+    pub fn hex_neighbors(
+        &self,
+        cell: [usize; 2],
+    ) -> impl Iterator<Item = ([usize; 2], &Option<SpriteCell>)> {
+        self.check_neighborhood(cell);
+        let axial = self.cell_to_axial(cell);
+        HEX_DIRECTIONS
+            .into_iter()
+            .filter_map(move |[dq, dr]| {
+                let [q, r] = axial?;
+                self.axial_to_cell([q + dq, r + dr])
+            })
+            .map(move |neighbor| (neighbor, &self[neighbor]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::prelude::*;
+
+    fn cells<'a>(
+        neighbors: impl Iterator<Item = ([usize; 2], &'a Option<SpriteCell>)>,
+    ) -> Vec<[usize; 2]> {
+        neighbors.map(|(cell, _)| cell).collect()
+    }
+
+    #[test]
+    fn neighbors_are_clipped_to_the_grid() {
+        let grid = SpriteGrid::empty(([5, 4], Vec2::ONE));
+        for (cell, four, eight, within_two) in [
+            ([0, 0], 2, 3, 8),
+            ([4, 3], 2, 3, 8),
+            ([2, 0], 3, 5, 14),
+            ([0, 2], 3, 5, 11),
+            ([2, 2], 4, 8, 19),
+        ] {
+            assert_eq!(grid.neighbors4(cell).count(), four, "{cell:?}");
+            assert_eq!(grid.neighbors8(cell).count(), eight, "{cell:?}");
+            assert_eq!(
+                grid.neighbors_within(cell, 2).count(),
+                within_two,
+                "{cell:?}"
+            );
+            assert_eq!(grid.neighbors4_wrapping(cell).count(), 4, "{cell:?}");
+            assert_eq!(grid.neighbors8_wrapping(cell).count(), 8, "{cell:?}");
+            assert_eq!(
+                grid.neighbors_within_wrapping(cell, 2).count(),
+                24,
+                "{cell:?}"
+            );
+        }
+        assert_eq!(cells(grid.neighbors4([0, 0])), vec![[1, 0], [0, 1]]);
+        assert_eq!(
+            cells(grid.neighbors8([2, 2])),
+            vec![
+                [1, 1],
+                [2, 1],
+                [3, 1],
+                [1, 2],
+                [3, 2],
+                [1, 3],
+                [2, 3],
+                [3, 3]
+            ]
+        );
+    }
+
+    #[test]
+    fn wrapping_neighbors_wrap_around_the_edges() {
+        let grid = SpriteGrid::empty(([5, 4], Vec2::ONE));
+        assert_eq!(
+            cells(grid.neighbors4_wrapping([0, 0])),
+            vec![[4, 0], [1, 0], [0, 3], [0, 1]]
+        );
+        assert_eq!(
+            cells(grid.neighbors8_wrapping([4, 3])),
+            vec![
+                [3, 2],
+                [4, 2],
+                [0, 2],
+                [3, 3],
+                [0, 3],
+                [3, 0],
+                [4, 0],
+                [0, 0]
+            ]
+        );
+        // the neighborhood is wider than the grid, so the cells wrap onto each other
+        let narrow = SpriteGrid::empty(([2, 1], Vec2::ONE));
+        assert_eq!(
+            cells(narrow.neighbors4_wrapping([0, 0])),
+            vec![[1, 0], [1, 0], [0, 0], [0, 0]]
+        );
+    }
+
+    #[test]
+    fn hex_cells_have_six_neighbors() {
+        let layout = SpriteGridLayout::Hex(HexLayout::pointy_top(HexCoordinates::OddOffset));
+        let grid = SpriteGrid::empty(([5, 5], Vec2::ONE, SpriteGridAlignment::default(), layout));
+        assert_eq!(grid.hex_neighbors([2, 2]).count(), 6);
+        assert_eq!(grid.hex_neighbors([0, 0]).count(), 2);
+        // odd rows are shoved to the right
+        assert_eq!(grid.hex_neighbors([4, 1]).count(), 3);
+        assert_eq!(grid.hex_neighbors([4, 2]).count(), 5);
+        let square = SpriteGrid::empty(([5, 5], Vec2::ONE));
+        assert_eq!(square.hex_neighbors([2, 2]).count(), 0);
+    }
+}
@@ -1,27 +1,24 @@
 use crate::prelude::*;
 use bevy::math::vec2;
 use bevy::prelude::*;
-use std::ops::Mul;
+use bevy::utils::HashSet;
 
+/// Transforms a world space point into the grid's local space.
+fn world_to_local(transform: &GlobalTransform, point: Vec2) -> Vec2 {
+    let m = transform.compute_matrix();
+    m.inverse().transform_point3(point.extend(0.0)).truncate()
+}
+
+/// The cell under `point`. Cells occupied by a multi-cell sprite
+/// report the sprite's origin, see [`SpriteGrid::place`].
 pub fn pick_cell(
     grid: &SpriteGrid,
     transform: &GlobalTransform,
     point: Vec2,
 ) -> Option<[usize; 2]> {
-    let alignment_translation = -grid.alignment.0 * grid.grid_size();
-    let grid_transform = transform.mul(Transform {
-        translation: alignment_translation.extend(0.0),
-        ..Default::default()
-    });
-    let m = grid_transform.compute_matrix();
-    let grid_point = m.inverse().transform_point3(point.extend(0.0)).truncate();
-    if 0.0 <= grid_point.x
-        && grid_point.x < grid.grid_size().x
-        && 0.0 <= grid_point.y
-        && grid_point.y < grid.grid_size().y
-    {
-        let cell = grid_point / grid.cell_size;
-        [cell.x as usize, cell.y as usize].into()
+    let [x, y] = pick_cell_unbounded(grid, transform, point);
+    if 0 <= x && x < grid.x_len() as i64 && 0 <= y && y < grid.y_len() as i64 {
+        grid.owner([x as usize, y as usize]).into()
     } else {
         None
     }
@@ -32,15 +29,7 @@ pub fn pick_cell_unbounded(
     transform: &GlobalTransform,
     point: Vec2,
 ) -> [i64; 2] {
-    let alignment_translation = -grid.alignment.0 * grid.grid_size();
-    let grid_transform = transform.mul(Transform {
-        translation: alignment_translation.extend(0.0),
-        ..Default::default()
-    });
-    let m = grid_transform.compute_matrix();
-    let grid_point = m.inverse().transform_point3(point.extend(0.0)).truncate();
-    let cell = grid_point / grid.cell_size;
-    [cell.x.floor() as i64, cell.y.floor() as i64]
+    grid.local_to_cell(world_to_local(transform, point))
 }
 
 pub fn pick_rect(
@@ -49,7 +38,7 @@ pub fn pick_rect(
     rect_half_size: Vec2,
     rect_transform: &GlobalTransform,
 ) -> Option<SpriteGridRect> {
-    if grid.x_len == 0 || grid.y_len == 0 {
+    if grid.x_len() == 0 || grid.y_len() == 0 {
         return None;
     }
     let cell_indices = [
@@ -79,22 +68,750 @@ pub fn pick_rect(
         };
     }
 
+    if grid.layout != SpriteGridLayout::Rectangular {
+        // cells of other layouts poke out past the cells at the corners
+        min_x -= 1;
+        max_x += 1;
+        min_y -= 1;
+        max_y += 1;
+    }
+
     if max_x < 0 {
         return None;
     }
     if max_y < 0 {
         return None;
     }
-    if grid.x_len as i64 <= min_x {
+    if grid.x_len() as i64 <= min_x {
         return None;
     }
-    if grid.y_len as i64 <= min_y {
+    if grid.y_len() as i64 <= min_y {
         return None;
     }
     Some(SpriteGridRect {
         left: min_x.max(0) as usize,
-        right: (max_x as usize + 1).clamp(0, grid.x_len),
+        right: (max_x as usize + 1).clamp(0, grid.x_len()),
         bottom: min_y.max(0) as usize,
-        top: (max_y as usize + 1).clamp(0, grid.y_len),
+        top: (max_y as usize + 1).clamp(0, grid.y_len()),
+    })
+}
+
+/// A cell crossed by a ray, see [`raycast_cells`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SpriteGridRayHit {
+    pub cell: [usize; 2],
+    /// World space distance along the ray at which it enters the cell,
+    /// zero for the cell containing the ray's origin.
+    pub distance: f32,
+}
+
+/// Walks every cell that a world space ray crosses, in order, using the
+/// Amanatidis-Woo grid traversal.
+///
+/// The walk ends after `max_distance` world units, or once `stop`
+/// returns true for a cell. That cell is included in the hits.
+///
+/// Grids with hexagonal or isometric layouts are walked from cell to cell
+/// across the edges the ray leaves each cell by.
+pub fn raycast_cells(
+    grid: &SpriteGrid,
+    transform: &GlobalTransform,
+    origin: Vec2,
+    direction: Vec2,
+    max_distance: f32,
+    mut stop: impl FnMut([usize; 2], &Option<SpriteCell>) -> bool,
+) -> Vec<SpriteGridRayHit> {
+    let mut hits = Vec::new();
+    if grid.x_len() == 0 || grid.y_len() == 0 || max_distance < 0.0 {
+        return hits;
+    }
+    let end = origin + direction.normalize_or_zero() * max_distance;
+    if grid.layout != SpriteGridLayout::Rectangular {
+        return raycast_polygons(grid, transform, origin, end, max_distance, stop);
+    }
+
+    // ray in cell coordinates, where the cell [x, y] covers [x, x + 1) * [y, y + 1)
+    let to_cells = |point: Vec2| {
+        (world_to_local(transform, point) - grid.cell_center([0, 0])) / grid.cell_size
+            + 0.5 * Vec2::ONE
+    };
+    let start = to_cells(origin);
+    let delta = to_cells(end) - start;
+    let size = vec2(grid.x_len() as f32, grid.y_len() as f32);
+
+    // clip the ray to the grid
+    let mut t_enter = 0.0f32;
+    let mut t_exit = 1.0f32;
+    for axis in 0..2 {
+        if delta[axis] == 0.0 {
+            if start[axis] < 0.0 || size[axis] <= start[axis] {
+                return hits;
+            }
+        } else {
+            let t0 = -start[axis] / delta[axis];
+            let t1 = (size[axis] - start[axis]) / delta[axis];
+            t_enter = t_enter.max(t0.min(t1));
+            t_exit = t_exit.min(t0.max(t1));
+        }
+    }
+    if t_exit < t_enter {
+        return hits;
+    }
+
+    let entry = start + t_enter * delta;
+    let mut cell = [0, 1].map(|axis| (entry[axis].floor() as i64).clamp(0, size[axis] as i64 - 1));
+    let step = [0, 1].map(|axis| delta[axis].signum() as i64);
+    let t_delta = [0, 1].map(|axis| 1.0 / delta[axis].abs());
+    let mut t_max = [0, 1].map(|axis| {
+        let boundary = if 0.0 < delta[axis] {
+            cell[axis] as f32 + 1.0
+        } else {
+            cell[axis] as f32
+        };
+        if delta[axis] == 0.0 {
+            f32::INFINITY
+        } else {
+            t_enter + (boundary - entry[axis]) / delta[axis]
+        }
+    });
+
+    let mut t = t_enter;
+    loop {
+        let current = [cell[0] as usize, cell[1] as usize];
+        hits.push(SpriteGridRayHit {
+            cell: current,
+            distance: t * max_distance,
+        });
+        if stop(current, &grid[current]) {
+            break;
+        }
+        let axis = if t_max[0] < t_max[1] { 0 } else { 1 };
+        t = t_max[axis];
+        if t_exit < t {
+            break;
+        }
+        cell[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+        if cell[axis] < 0 || size[axis] as i64 <= cell[axis] {
+            break;
+        }
+    }
+    hits
+}
+
+/// Walks the ray from `origin` to `end` through the hexagons or diamonds of the grid.
+///
+/// The ray leaves each cell through one of the cell's edges, and enters the cell
+/// on the other side of that edge, whose center is the reflection of the cell's
+/// center through the middle of the edge.
+fn raycast_polygons(
+    grid: &SpriteGrid,
+    transform: &GlobalTransform,
+    origin: Vec2,
+    end: Vec2,
+    max_distance: f32,
+    mut stop: impl FnMut([usize; 2], &Option<SpriteCell>) -> bool,
+) -> Vec<SpriteGridRayHit> {
+    let mut hits = Vec::new();
+    let start = world_to_local(transform, origin);
+    let delta = world_to_local(transform, end) - start;
+
+    // clip the ray to the bounding box of the cells at the corners of the grid,
+    // grown by a cell to cover the cells poking out past them
+    let [last_x, last_y] = [grid.x_len() as i64 - 1, grid.y_len() as i64 - 1];
+    let (min, max) = [[0, 0], [last_x, 0], [0, last_y], [last_x, last_y]]
+        .into_iter()
+        .map(|cell| grid.center_at(cell))
+        .fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(lo, hi), center| (lo.min(center), hi.max(center)),
+        );
+    let min = min - grid.cell_size;
+    let max = max + grid.cell_size;
+    let mut t_enter = 0.0f32;
+    let mut t_exit = 1.0f32;
+    for axis in 0..2 {
+        if delta[axis] == 0.0 {
+            if start[axis] < min[axis] || max[axis] < start[axis] {
+                return hits;
+            }
+        } else {
+            let t0 = (min[axis] - start[axis]) / delta[axis];
+            let t1 = (max[axis] - start[axis]) / delta[axis];
+            t_enter = t_enter.max(t0.min(t1));
+            t_exit = t_exit.min(t0.max(t1));
+        }
+    }
+    if t_exit < t_enter {
+        return hits;
+    }
+
+    let corners = grid.layout.corners(grid.cell_size);
+    let mut t = t_enter;
+    let mut cell = grid.local_to_cell(start + t * delta);
+    // every step crosses an edge inside the bounding box, the bound only guards against rounding
+    let steps = 4 * (grid.x_len() + grid.y_len()) + 16;
+    for _ in 0..steps {
+        if let Some(current) = grid.offset([0, 0], cell) {
+            hits.push(SpriteGridRayHit {
+                cell: current,
+                distance: t * max_distance,
+            });
+            if stop(current, &grid[current]) {
+                break;
+            }
+        }
+        // the ray leaves the cell through the edge it crosses first
+        let center = grid.center_at(cell);
+        let (mut t_edge, mut a, mut b) = (f32::INFINITY, center, center);
+        for i in 0..corners.len() {
+            let from = center + corners[i];
+            let to = center + corners[(i + 1) % corners.len()];
+            // the corners are counter-clockwise, so this points out of the cell
+            let normal = -(to - from).perp();
+            let approach = normal.dot(delta);
+            if 0.0 < approach {
+                let t_crossing = normal.dot(from - start) / approach;
+                if t_crossing < t_edge {
+                    (t_edge, a, b) = (t_crossing, from, to);
+                }
+            }
+        }
+        // the walk ends with the ray, a ray that doesn't move never leaves its cell
+        if t_exit <= t_edge {
+            break;
+        }
+        let next = grid.local_to_cell(a + b - center);
+        if next == cell {
+            break;
+        }
+        t = t.max(t_edge);
+        cell = next;
+    }
+    hits
+}
+
+/// The corners of the cell in world space, counter-clockwise in the grid's local space.
+fn cell_corners(grid: &SpriteGrid, transform: &GlobalTransform, cell: [usize; 2]) -> Vec<Vec2> {
+    grid.cell_corners(cell)
+        .into_iter()
+        .map(|corner| transform.mul_vec3(corner.extend(0.0)).truncate())
+        .collect()
+}
+
+fn project(polygon: &[Vec2], axis: Vec2) -> (f32, f32) {
+    polygon
+        .iter()
+        .map(|point| point.dot(axis))
+        .fold((f32::MAX, f32::MIN), |(lo, hi), d| (lo.min(d), hi.max(d)))
+}
+
+/// Separating axis test, polygons that only touch don't overlap.
+fn convex_polygons_overlap(a: &[Vec2], b: &[Vec2]) -> bool {
+    [a, b].iter().all(|polygon| {
+        (0..polygon.len()).all(|i| {
+            let edge = polygon[(i + 1) % polygon.len()] - polygon[i];
+            let axis = edge.perp();
+            let (a_min, a_max) = project(a, axis);
+            let (b_min, b_max) = project(b, axis);
+            b_min < a_max && a_min < b_max
+        })
+    })
+}
+
+fn convex_polygon_circle_overlap(polygon: &[Vec2], center: Vec2, radius: f32) -> bool {
+    let mut inside = [true, true];
+    for i in 0..polygon.len() {
+        let a = polygon[i];
+        let b = polygon[(i + 1) % polygon.len()];
+        let edge = b - a;
+        let t = ((center - a).dot(edge) / edge.length_squared()).clamp(0.0, 1.0);
+        if center.distance_squared(a + t * edge) < radius * radius {
+            return true;
+        }
+        let side = edge.perp_dot(center - a);
+        inside[0] &= 0.0 <= side;
+        inside[1] &= side <= 0.0;
+    }
+    inside[0] || inside[1]
+}
+
+/// The cells inside `candidates` whose world space corners satisfy `overlaps`.
+/// Cells occupied by a multi-cell sprite report the sprite's origin, once.
+fn pick_exact(
+    grid: &SpriteGrid,
+    transform: &GlobalTransform,
+    candidates: Option<SpriteGridRect>,
+    mut overlaps: impl FnMut(&[Vec2]) -> bool,
+) -> Vec<[usize; 2]> {
+    let mut cells = Vec::new();
+    let mut owners = HashSet::default();
+    if let Some(candidates) = candidates {
+        for y in candidates.ys() {
+            for x in candidates.xs() {
+                let owner = grid.owner([x, y]);
+                if owners.contains(&owner) {
+                    continue;
+                }
+                if overlaps(&cell_corners(grid, transform, [x, y])) {
+                    owners.insert(owner);
+                    cells.push(owner);
+                }
+            }
+        }
+    }
+    cells
+}
+
+/// Every cell overlapped by the world space circle, in row-major order.
+/// Cells occupied by a multi-cell sprite report the sprite's origin, once.
+pub fn pick_circle(
+    grid: &SpriteGrid,
+    transform: &GlobalTransform,
+    center: Vec2,
+    radius: f32,
+) -> Vec<[usize; 2]> {
+    let candidates = pick_rect(
+        grid,
+        transform,
+        Vec2::splat(radius),
+        &GlobalTransform::from_translation(center.extend(0.0)),
+    );
+    pick_exact(grid, transform, candidates, |corners| {
+        convex_polygon_circle_overlap(corners, center, radius)
     })
 }
+
+/// Every cell overlapped by the convex polygon with the given world space
+/// vertices, in row-major order.
+/// Cells occupied by a multi-cell sprite report the sprite's origin, once.
+pub fn pick_polygon(
+    grid: &SpriteGrid,
+    transform: &GlobalTransform,
+    vertices: &[Vec2],
+) -> Vec<[usize; 2]> {
+    if vertices.is_empty() {
+        return Vec::new();
+    }
+    let (min, max) = vertices.iter().fold(
+        (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+        |(lo, hi), &v| (lo.min(v), hi.max(v)),
+    );
+    let candidates = pick_rect(
+        grid,
+        transform,
+        0.5 * (max - min),
+        &GlobalTransform::from_translation((0.5 * (min + max)).extend(0.0)),
+    );
+    pick_exact(grid, transform, candidates, |corners| {
+        convex_polygons_overlap(corners, vertices)
+    })
+}
+
+/// Every cell overlapped by the rectangle, in row-major order.
+/// Cells occupied by a multi-cell sprite report the sprite's origin, once.
+///
+/// Unlike [`pick_rect`], which returns the bounding rect of the cells,
+/// this only returns cells that the rectangle actually overlaps when
+/// it is rotated relative to the grid.
+pub fn pick_oriented_rect(
+    grid: &SpriteGrid,
+    transform: &GlobalTransform,
+    rect_half_size: Vec2,
+    rect_transform: &GlobalTransform,
+) -> Vec<[usize; 2]> {
+    let corners = [
+        -rect_half_size,
+        rect_half_size * vec2(1.0, -1.0),
+        rect_half_size,
+        rect_half_size * vec2(-1.0, 1.0),
+    ]
+    .map(|corner| rect_transform.mul_vec3(corner.extend(0.0)).truncate());
+    let candidates = pick_rect(grid, transform, rect_half_size, rect_transform);
+    pick_exact(grid, transform, candidates, |cell_corners| {
+        convex_polygons_overlap(cell_corners, &corners)
+    })
+}
+
+/// Which cells [`pick_all`] reports.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpriteGridPickFilter {
+    /// Every cell, including empty ones.
+    All,
+    /// Only cells holding a sprite.
+    NonEmpty,
+    /// Only cells holding a sprite whose color isn't fully transparent.
+    Opaque,
+}
+
+impl Default for SpriteGridPickFilter {
+    fn default() -> Self {
+        Self::NonEmpty
+    }
+}
+
+impl SpriteGridPickFilter {
+    pub fn accepts(self, cell: &Option<SpriteCell>) -> bool {
+        match (self, cell) {
+            (Self::All, _) => true,
+            (_, None) => false,
+            (Self::NonEmpty, Some(_)) => true,
+            (Self::Opaque, Some(SpriteCell::Color(color))) => 0.0 < color.a(),
+            (Self::Opaque, Some(SpriteCell::Texture(cell))) => 0.0 < cell.color.a(),
+        }
+    }
+}
+
+/// The cell under `point` of each grid that has one accepted by `filter`,
+/// topmost first, ordered by the grids' global z.
+pub fn pick_all<'a>(
+    grids: impl IntoIterator<Item = (Entity, &'a SpriteGrid, &'a GlobalTransform)>,
+    point: Vec2,
+    filter: SpriteGridPickFilter,
+) -> Vec<(Entity, [usize; 2])> {
+    let mut picked: Vec<_> = grids
+        .into_iter()
+        .filter_map(|(entity, grid, transform)| {
+            pick_cell(grid, transform, point)
+                .filter(|&cell| filter.accepts(&grid[cell]))
+                .map(|cell| (entity, cell, transform.translation().z))
+        })
+        .collect();
+    picked.sort_by(|a, b| b.2.total_cmp(&a.2));
+    picked
+        .into_iter()
+        .map(|(entity, cell, _)| (entity, cell))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid() -> SpriteGrid {
+        SpriteGrid::empty(([10, 10], Vec2::ONE, SpriteGridAlignment::bottom_left()))
+    }
+
+    fn cells(hits: &[SpriteGridRayHit]) -> Vec<[usize; 2]> {
+        hits.iter().map(|hit| hit.cell).collect()
+    }
+
+    #[test]
+    fn raycast_visits_cells_in_order() {
+        let hits = raycast_cells(
+            &grid(),
+            &GlobalTransform::default(),
+            vec2(0.5, 0.5),
+            vec2(1.0, 0.5),
+            20.0,
+            |_, _| false,
+        );
+        assert_eq!(
+            cells(&hits),
+            [
+                [0, 0],
+                [1, 0],
+                [1, 1],
+                [2, 1],
+                [3, 1],
+                [3, 2],
+                [4, 2],
+                [5, 2],
+                [5, 3],
+                [6, 3],
+                [7, 3],
+                [7, 4],
+                [8, 4],
+                [9, 4],
+                [9, 5],
+            ]
+        );
+        assert!(hits
+            .windows(2)
+            .all(|pair| pair[0].distance <= pair[1].distance));
+    }
+
+    #[test]
+    fn raycast_enters_from_outside_and_stops() {
+        let mut grid = grid();
+        grid.set([4, 3], Color::WHITE);
+        let hits = raycast_cells(
+            &grid,
+            &GlobalTransform::default(),
+            vec2(-2.0, 3.5),
+            Vec2::X,
+            20.0,
+            |_, cell| cell.is_some(),
+        );
+        assert_eq!(cells(&hits), [[0, 3], [1, 3], [2, 3], [3, 3], [4, 3]]);
+        assert!((hits[0].distance - 2.0).abs() < 1e-4);
+
+        // walking back the other way visits the same cells in reverse
+        let hits = raycast_cells(
+            &grid,
+            &GlobalTransform::default(),
+            vec2(4.5, 3.5),
+            -Vec2::X,
+            20.0,
+            |_, _| false,
+        );
+        assert_eq!(cells(&hits), [[4, 3], [3, 3], [2, 3], [1, 3], [0, 3]]);
+    }
+
+    /// Whether the segment from `a` to `b` crosses the convex, counter-clockwise `polygon`.
+    fn segment_crosses(polygon: &[Vec2], a: Vec2, b: Vec2) -> bool {
+        let (mut t0, mut t1) = (0.0f32, 1.0f32);
+        for i in 0..polygon.len() {
+            let p = polygon[i];
+            let edge = polygon[(i + 1) % polygon.len()] - p;
+            // positive inside of the edge
+            let from = edge.perp_dot(a - p);
+            let along = edge.perp_dot(b - a);
+            if along == 0.0 {
+                if from < 0.0 {
+                    return false;
+                }
+            } else if 0.0 < along {
+                t0 = t0.max(-from / along);
+            } else {
+                t1 = t1.min(-from / along);
+            }
+        }
+        t0 <= t1
+    }
+
+    #[test]
+    fn polygon_raycasts_match_brute_force() {
+        let transform = GlobalTransform::from(
+            Transform::from_xyz(-4.0, 7.0, 0.0)
+                .mul_transform(Transform::from_rotation(Quat::from_rotation_z(0.7))),
+        );
+        // a small linear congruential generator, to keep the rays reproducible
+        let mut seed = 7u64;
+        let mut random = move || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 40) as f32 / (1u64 << 24) as f32
+        };
+        for layout in [
+            SpriteGridLayout::Hex(HexLayout::pointy_top(HexCoordinates::OddOffset)),
+            SpriteGridLayout::Hex(HexLayout::pointy_top(HexCoordinates::EvenOffset)),
+            SpriteGridLayout::Hex(HexLayout::pointy_top(HexCoordinates::Axial)),
+            SpriteGridLayout::Hex(HexLayout::flat_top(HexCoordinates::OddOffset)),
+            SpriteGridLayout::Hex(HexLayout::flat_top(HexCoordinates::EvenOffset)),
+            SpriteGridLayout::Hex(HexLayout::flat_top(HexCoordinates::Axial)),
+            SpriteGridLayout::Isometric,
+            SpriteGridLayout::StaggeredIsometric(StaggerIndex::Odd),
+            SpriteGridLayout::StaggeredIsometric(StaggerIndex::Even),
+        ] {
+            let grid = SpriteGrid::empty((
+                [9, 7],
+                vec2(3.0, 2.0),
+                SpriteGridAlignment::center(),
+                layout,
+            ));
+            for _ in 0..200 {
+                let origin = vec2(random() - 0.5, random() - 0.5) * 60.0;
+                let angle = random() * std::f32::consts::TAU;
+                let direction = vec2(angle.cos(), angle.sin());
+                let max_distance = random() * 60.0;
+                let hits = raycast_cells(
+                    &grid,
+                    &transform,
+                    origin,
+                    direction,
+                    max_distance,
+                    |_, _| false,
+                );
+
+                // the cells the segment crosses, with each cell shrunk or grown a little
+                let a = world_to_local(&transform, origin);
+                let b = world_to_local(&transform, origin + direction * max_distance);
+                let crossed = |scale: f32| {
+                    let mut cells = HashSet::default();
+                    for y in 0..grid.y_len() {
+                        for x in 0..grid.x_len() {
+                            let center = grid.cell_center([x, y]);
+                            let corners: Vec<_> = grid
+                                .cell_corners([x, y])
+                                .into_iter()
+                                .map(|corner| center + (corner - center) * scale)
+                                .collect();
+                            if segment_crosses(&corners, a, b) {
+                                cells.insert([x, y]);
+                            }
+                        }
+                    }
+                    cells
+                };
+                let surely = crossed(0.999);
+                let maybe = crossed(1.001);
+                let unique: HashSet<_> = cells(&hits).into_iter().collect();
+                assert_eq!(unique.len(), hits.len(), "{layout:?} {origin} {direction}");
+                assert!(
+                    surely.is_subset(&unique),
+                    "{layout:?} {surely:?} {unique:?}"
+                );
+                assert!(unique.is_subset(&maybe), "{layout:?} {unique:?} {maybe:?}");
+                assert!(hits
+                    .windows(2)
+                    .all(|pair| pair[0].distance <= pair[1].distance));
+            }
+        }
+    }
+
+    /// A rotated, scaled grid with a multi-cell sprite, and its transform.
+    fn rotated_grid() -> (SpriteGrid, GlobalTransform) {
+        let mut grid = SpriteGrid::empty(([10, 8], vec2(2.0, 3.0), SpriteGridAlignment::center()));
+        grid.place([4, 3], [2, 2], Color::WHITE);
+        let transform = Transform::from_xyz(3.0, -2.0, 0.0)
+            .mul_transform(Transform::from_rotation(Quat::from_rotation_z(0.4)))
+            .mul_transform(Transform::from_scale(Vec3::splat(1.5)));
+        (grid, transform.into())
+    }
+
+    /// The owners of the cells with a sample point for which `inside` is true,
+    /// sampling each cell on an `n` by `n` grid of points that spans the cell
+    /// from edge to edge, or stays `inset` cells away from its edges.
+    fn sampled(
+        grid: &SpriteGrid,
+        transform: &GlobalTransform,
+        n: usize,
+        inset: f32,
+        inside: impl Fn(Vec2) -> bool,
+    ) -> HashSet<[usize; 2]> {
+        let mut cells = HashSet::default();
+        for y in 0..grid.y_len() {
+            for x in 0..grid.x_len() {
+                let center = grid.cell_center([x, y]);
+                let hit = (0..n).any(|j| {
+                    (0..n).any(|i| {
+                        let f = |k: usize| inset + (1.0 - 2.0 * inset) * k as f32 / (n - 1) as f32;
+                        let local = center + (vec2(f(i), f(j)) - 0.5) * grid.cell_size;
+                        inside(transform.mul_vec3(local.extend(0.0)).truncate())
+                    })
+                });
+                if hit {
+                    cells.insert(grid.owner([x, y]));
+                }
+            }
+        }
+        cells
+    }
+
+    /// Checks that `picked` has every owner of a cell with a point strictly inside
+    /// the shape, only owners of cells with a point close to the shape, and no duplicates.
+    fn assert_matches_sampling(
+        picked: Vec<[usize; 2]>,
+        grid: &SpriteGrid,
+        transform: &GlobalTransform,
+        inside: impl Fn(Vec2, f32) -> bool,
+    ) {
+        let unique: HashSet<_> = picked.iter().copied().collect();
+        assert_eq!(unique.len(), picked.len());
+        let surely = sampled(grid, transform, 8, 0.01, |point| inside(point, -0.01));
+        let maybe = sampled(grid, transform, 40, 0.0, |point| inside(point, 0.05));
+        assert!(!surely.is_empty());
+        assert!(surely.is_subset(&unique), "{surely:?} {unique:?}");
+        assert!(unique.is_subset(&maybe), "{unique:?} {maybe:?}");
+    }
+
+    #[test]
+    fn area_picks_match_point_sampling() {
+        let (grid, transform) = rotated_grid();
+        for (center, radius) in [
+            (vec2(3.0, -2.0), 2.5),
+            (vec2(-9.0, 4.0), 6.0),
+            (vec2(14.0, 7.0), 9.5),
+            (vec2(1.0, 1.0), 1.2),
+        ] {
+            let picked = pick_circle(&grid, &transform, center, radius);
+            assert_matches_sampling(picked, &grid, &transform, |point, margin| {
+                point.distance(center) < radius + margin
+            });
+        }
+
+        // a triangle, counter-clockwise
+        let vertices = [vec2(-12.0, -3.0), vec2(6.0, -10.0), vec2(2.0, 9.0)];
+        let picked = pick_polygon(&grid, &transform, &vertices);
+        assert_matches_sampling(picked, &grid, &transform, |point, margin| {
+            (0..3).all(|i| {
+                let a = vertices[i];
+                let edge = vertices[(i + 1) % 3] - a;
+                -margin * edge.length() < edge.perp_dot(point - a)
+            })
+        });
+
+        for (translation, angle, half_size) in [
+            (vec2(3.0, -2.0), 0.0, vec2(4.0, 1.0)),
+            (vec2(-6.0, 5.0), 1.1, vec2(7.0, 2.5)),
+            (vec2(10.0, -8.0), -0.5, vec2(3.0, 9.0)),
+        ] {
+            let rect_transform = GlobalTransform::from(
+                Transform::from_translation(translation.extend(0.0))
+                    .mul_transform(Transform::from_rotation(Quat::from_rotation_z(angle))),
+            );
+            let picked = pick_oriented_rect(&grid, &transform, half_size, &rect_transform);
+            let to_rect = rect_transform.compute_matrix().inverse();
+            assert_matches_sampling(picked, &grid, &transform, |point, margin| {
+                let local = to_rect.transform_point3(point.extend(0.0)).truncate();
+                local.abs().cmplt(half_size + margin).all()
+            });
+        }
+    }
+
+    #[test]
+    fn pick_all_puts_the_topmost_grid_first() {
+        let filled = |color: Color| SpriteGrid::from_cell(([4, 4], Vec2::ONE), color);
+        let mut holed = filled(Color::RED);
+        holed[[1, 1]] = None;
+        let transparent = filled(Color::rgba(1.0, 1.0, 1.0, 0.0));
+        let grids = [
+            (Entity::from_raw(0), filled(Color::WHITE), 0.0),
+            (Entity::from_raw(1), holed, 5.0),
+            (Entity::from_raw(2), filled(Color::GREEN), -3.0),
+            (Entity::from_raw(3), transparent, 2.0),
+            (Entity::from_raw(4), filled(Color::BLUE), 1.0),
+        ];
+        let transforms: Vec<_> = grids
+            .iter()
+            .map(|&(entity, _, z)| {
+                // the last grid is moved away from the picked point
+                let x = if entity == Entity::from_raw(4) {
+                    10.0
+                } else {
+                    0.0
+                };
+                GlobalTransform::from_xyz(x, 0.0, z)
+            })
+            .collect();
+        let pick = |point: Vec2, filter| {
+            let grids = grids
+                .iter()
+                .zip(&transforms)
+                .map(|((entity, grid, _), transform)| (*entity, grid, transform));
+            pick_all(grids, point, filter)
+                .into_iter()
+                .map(|(entity, cell)| (entity.id(), cell))
+                .collect::<Vec<_>>()
+        };
+        let point = vec2(1.5, 1.5);
+        assert_eq!(
+            pick(point, SpriteGridPickFilter::All),
+            [(1, [1, 1]), (3, [1, 1]), (0, [1, 1]), (2, [1, 1])]
+        );
+        assert_eq!(
+            pick(point, SpriteGridPickFilter::NonEmpty),
+            [(3, [1, 1]), (0, [1, 1]), (2, [1, 1])]
+        );
+        assert_eq!(
+            pick(point, SpriteGridPickFilter::Opaque),
+            [(0, [1, 1]), (2, [1, 1])]
+        );
+        assert_eq!(
+            pick(vec2(2.5, 0.5), SpriteGridPickFilter::Opaque),
+            [(1, [2, 0]), (0, [2, 0]), (2, [2, 0])]
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use crate::prelude::*;
+
+/// How the cells of a pasted grid are combined with the cells they cover.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpriteGridBlendMode {
+    /// Every cell is replaced, including by empty cells.
+    Overwrite,
+    /// Only the non-empty cells of the pasted grid are copied.
+    NonEmpty,
+}
+
+impl Default for SpriteGridBlendMode {
+    fn default() -> Self {
+        Self::Overwrite
+    }
+}
+
+impl SpriteGrid {
+    fn check_rect(&self, rect: SpriteGridRect) {
+        assert!(
+            rect.left <= rect.right
+                && rect.bottom <= rect.top
+                && rect.right <= self.x_len()
+                && rect.top <= self.y_len(),
+            "{rect:?} is outside of the {}x{} sprite grid",
+            self.x_len(),
+            self.y_len()
+        );
+    }
+
+    /// Removes the multi-cell sprites whose origins are outside of `rect`
+    /// but that cover some of its cells.
+    fn remove_multi_cells_reaching_into(&mut self, rect: SpriteGridRect) {
+        let reaching: Vec<_> = self
+            .multi_cells()
+            .filter(|&([x, y], [w, h])| {
+                let area = SpriteGridRect {
+                    left: x,
+                    right: x + w,
+                    bottom: y,
+                    top: y + h,
+                };
+                !rect.contains([x, y]) && rect.intersect_with(area).is_some()
+            })
+            .map(|(origin, _)| origin)
+            .collect();
+        for origin in reaching {
+            self.remove_multi_cell(origin);
+        }
+    }
+
+    /// A new grid holding a copy of the cells and cell transforms inside `rect`,
+    /// and of the multi-cell sprites whose origins are inside it, clipped to `rect`.
+    /// The new grid has the same cell size, alignment, layout, culling, sorting and storage.
+    pub fn copy_region(&self, rect: SpriteGridRect) -> SpriteGrid {
+        self.check_rect(rect);
+        let mut region = SpriteGrid::empty((
+            [rect.width(), rect.height()],
+            self.cell_size,
+            self.alignment,
+            self.layout,
+        ));
+        region.culling = self.culling;
+        region.sorting = self.sorting;
+        region.set_storage(self.storage());
+        for ([x, y], cell) in self.iter(rect.xs(), rect.ys()) {
+            region[[x - rect.left, y - rect.bottom]] = Some(cell.clone());
+        }
+        for ([x, y], &transform) in self.cell_transforms() {
+            if rect.contains([x, y]) {
+                region.set_cell_transform([x - rect.left, y - rect.bottom], transform);
+            }
+        }
+        for ([x, y], [w, h]) in self.multi_cells() {
+            if let (true, Some(cell)) = (rect.contains([x, y]), &self[[x, y]]) {
+                let footprint = [w.min(rect.right - x), h.min(rect.top - y)];
+                region.place([x - rect.left, y - rect.bottom], footprint, cell.clone());
+            }
+        }
+        region
+    }
+
+    /// Copies the cells of `other` into this grid, with `other`'s cell `[0, 0]`
+    /// placed at `at`. Cells that fall outside of this grid are clipped.
+    ///
+    /// Multi-cell sprites whose origins or occupied cells are overwritten are removed,
+    /// with [`SpriteGridBlendMode::Overwrite`] that's every multi-cell sprite covering
+    /// the pasted area. The multi-cell sprites of `other` are placed again, clipped to this grid.
+    pub fn paste(&mut self, other: &SpriteGrid, at: [usize; 2], mode: SpriteGridBlendMode) {
+        let target = SpriteGridRect {
+            left: at[0],
+            right: at[0].saturating_add(other.x_len()),
+            bottom: at[1],
+            top: at[1].saturating_add(other.y_len()),
+        };
+        let target = match target.intersect_with(self.bounds()) {
+            Some(target) => target,
+            None => return,
+        };
+        if mode == SpriteGridBlendMode::Overwrite {
+            self.remove_multi_cells_reaching_into(target);
+        }
+        for y in target.ys() {
+            for x in target.xs() {
+                let source = [x - at[0], y - at[1]];
+                match (&other[source], mode) {
+                    (Some(cell), _) => self.put([x, y], Some(cell.clone())),
+                    (None, SpriteGridBlendMode::Overwrite) => self.put([x, y], None),
+                    (None, SpriteGridBlendMode::NonEmpty) => continue,
+                }
+                if other.has_cell_transform(source) {
+                    self.set_cell_transform([x, y], other.cell_transform(source));
+                } else {
+                    self.remove_cell_transform([x, y]);
+                }
+            }
+        }
+        for ([x, y], [w, h]) in other.multi_cells() {
+            let origin = [x + at[0], y + at[1]];
+            if let (true, Some(cell)) = (target.contains(origin), &other[[x, y]]) {
+                let footprint = [
+                    w.min(target.right - origin[0]),
+                    h.min(target.top - origin[1]),
+                ];
+                self.place(origin, footprint, cell.clone());
+            }
+        }
+        debug_assert!(self.footprints_are_consistent());
+    }
+
+    /// Swaps the cells in `rect` with the cells of the same sized rect
+    /// whose bottom left cell is at `other`.
+    ///
+    /// Multi-cell sprites whose origins are inside either rect are moved
+    /// along with their origins, clipped to the rect they are moved into.
+    /// Multi-cell sprites reaching into either rect from outside are removed.
+    ///
+    /// Panics if the rects overlap or either is outside of the grid.
+    pub fn swap_regions(&mut self, rect: SpriteGridRect, other: [usize; 2]) {
+        let other = SpriteGridRect {
+            left: other[0],
+            right: other[0] + rect.width(),
+            bottom: other[1],
+            top: other[1] + rect.height(),
+        };
+        self.check_rect(rect);
+        self.check_rect(other);
+        assert!(
+            rect.width() == 0 || rect.height() == 0 || rect.intersect_with(other).is_none(),
+            "can't swap the overlapping regions {rect:?} and {other:?}"
+        );
+        let moved = |[x, y]: [usize; 2]| {
+            if rect.contains([x, y]) {
+                [x - rect.left + other.left, y - rect.bottom + other.bottom]
+            } else {
+                [x - other.left + rect.left, y - other.bottom + rect.bottom]
+            }
+        };
+        self.remove_multi_cells_reaching_into(rect);
+        self.remove_multi_cells_reaching_into(other);
+        let multi_cells: Vec<_> = self
+            .multi_cells()
+            .filter(|&(origin, _)| rect.contains(origin) || other.contains(origin))
+            .collect();
+        let multi_cells: Vec<_> = multi_cells
+            .into_iter()
+            .filter_map(|(origin, footprint)| {
+                Some((origin, footprint, self.remove_multi_cell(origin)?))
+            })
+            .collect();
+        for y in 0..rect.height() {
+            for x in 0..rect.width() {
+                let a = [rect.left + x, rect.bottom + y];
+                let b = [other.left + x, other.bottom + y];
+                if self[a].is_none() && self[b].is_none() {
+                    continue;
+                }
+                let cell = self[a].take();
+                self[a] = self[b].take();
+                self[b] = cell;
+            }
+        }
+        let transforms: Vec<_> = self
+            .cell_transforms()
+            .filter(|&(cell, _)| rect.contains(cell) || other.contains(cell))
+            .map(|(cell, &transform)| (cell, transform))
+            .collect();
+        for &(cell, _) in &transforms {
+            self.remove_cell_transform(cell);
+        }
+        for (cell, transform) in transforms {
+            self.set_cell_transform(moved(cell), transform);
+        }
+        for (origin, [w, h], sprite) in multi_cells {
+            let [x, y] = moved(origin);
+            let into = if rect.contains([x, y]) { rect } else { other };
+            self.place([x, y], [w.min(into.right - x), h.min(into.top - y)], sprite);
+        }
+        debug_assert!(self.footprints_are_consistent());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::prelude::*;
+    use std::ops::Range;
+
+    /// A cell that remembers where it was written.
+    fn marked([x, y]: [usize; 2]) -> SpriteCell {
+        SpriteCell::Color(Color::rgb(x as f32, y as f32, 0.0))
+    }
+
+    fn mark(cell: &Option<SpriteCell>) -> Option<[usize; 2]> {
+        match cell {
+            Some(SpriteCell::Color(color)) => Some([color.r() as usize, color.g() as usize]),
+            _ => None,
+        }
+    }
+
+    fn rect(xs: Range<usize>, ys: Range<usize>) -> SpriteGridRect {
+        SpriteGridRect {
+            left: xs.start,
+            right: xs.end,
+            bottom: ys.start,
+            top: ys.end,
+        }
+    }
+
+    fn marked_grid(size: [usize; 2]) -> SpriteGrid {
+        SpriteGrid::from_fn((size, Vec2::ONE), |cell| Some(marked(cell)))
+    }
+
+    #[test]
+    fn copied_regions_clip_their_multi_cells() {
+        let mut grid = marked_grid([10, 8]);
+        grid.place([2, 2], [3, 3], marked([2, 2]));
+        grid.place([0, 5], [2, 2], marked([0, 5]));
+        grid.set_cell_transform([3, 1], Transform::from_xyz(1.0, 0.0, 0.0));
+
+        let region = grid.copy_region(rect(1..4, 1..6));
+        assert!(region.footprints_are_consistent());
+        assert_eq!([region.x_len(), region.y_len()], [3, 5]);
+        // the sprite at [2, 2] is clipped, the one at [0, 5] isn't copied
+        assert_eq!(
+            region.multi_cells().collect::<Vec<_>>(),
+            vec![([1, 1], [2, 3])]
+        );
+        assert_eq!(mark(&region[[0, 4]]), None);
+        assert_eq!(mark(&region[[0, 0]]), Some([1, 1]));
+        assert_eq!(region.cell_transform([2, 0]).translation.x, 1.0);
+        assert_eq!(region.cell_transforms().count(), 1);
+    }
+
+    #[test]
+    fn pasting_removes_the_multi_cells_it_covers() {
+        let mut stamp = SpriteGrid::empty(([3, 3], Vec2::ONE));
+        stamp.set([1, 1], marked([50, 50]));
+        stamp.place([0, 2], [3, 1], marked([60, 60]));
+
+        for mode in [
+            SpriteGridBlendMode::Overwrite,
+            SpriteGridBlendMode::NonEmpty,
+        ] {
+            let mut grid = marked_grid([8, 8]);
+            // reaches into the pasted area from the left, below an empty cell of the stamp
+            grid.place([1, 3], [3, 1], marked([1, 3]));
+            // covers the cell the stamp's sprite is pasted to
+            grid.place([5, 3], [2, 2], marked([5, 3]));
+            grid.paste(&stamp, [3, 2], mode);
+            assert!(grid.footprints_are_consistent(), "{mode:?}");
+
+            assert_eq!(mark(&grid[[4, 3]]), Some([50, 50]));
+            assert_eq!(mark(&grid[[5, 3]]), None);
+            // the stamp's multi-cell sprite is clipped to the grid
+            let mut multi_cells: Vec<_> = grid.multi_cells().collect();
+            multi_cells.sort();
+            let kept = match mode {
+                // every cell of the pasted area is replaced
+                SpriteGridBlendMode::Overwrite => {
+                    assert_eq!(mark(&grid[[1, 3]]), None);
+                    assert_eq!(mark(&grid[[3, 2]]), None);
+                    vec![([3, 4], [3, 1])]
+                }
+                // the stamp's empty cell at [3, 3] leaves the sprite at [1, 3] alone
+                SpriteGridBlendMode::NonEmpty => {
+                    assert_eq!(mark(&grid[[1, 3]]), Some([1, 3]));
+                    assert_eq!(mark(&grid[[3, 2]]), Some([3, 2]));
+                    vec![([1, 3], [3, 1]), ([3, 4], [3, 1])]
+                }
+            };
+            assert_eq!(multi_cells, kept, "{mode:?}");
+        }
+
+        // pasting at the edge clips the stamp's multi-cell sprite
+        let mut grid = marked_grid([8, 8]);
+        grid.paste(&stamp, [6, 5], SpriteGridBlendMode::Overwrite);
+        assert!(grid.footprints_are_consistent());
+        assert_eq!(
+            grid.multi_cells().collect::<Vec<_>>(),
+            vec![([6, 7], [2, 1])]
+        );
+    }
+
+    #[test]
+    fn swapping_regions_moves_or_removes_multi_cells() {
+        let mut grid = marked_grid([10, 4]);
+        // reaches into the swapped rect from outside
+        grid.place([0, 0], [3, 2], marked([0, 0]));
+        // inside the other rect
+        grid.place([6, 0], [2, 2], marked([6, 0]));
+        grid.set_cell_transform([7, 1], Transform::from_xyz(1.0, 0.0, 0.0));
+
+        grid.swap_regions(rect(1..3, 0..2), [6, 0]);
+        assert!(grid.footprints_are_consistent());
+        assert_eq!(grid.owner([1, 0]), [1, 0]);
+        assert_eq!(mark(&grid[[0, 0]]), None);
+        assert_eq!(
+            grid.multi_cells().collect::<Vec<_>>(),
+            vec![([1, 0], [2, 2])]
+        );
+        assert_eq!(mark(&grid[[1, 0]]), Some([6, 0]));
+        // the cells swapped in from under the removed sprite are empty
+        assert_eq!(mark(&grid[[6, 0]]), None);
+        assert_eq!(mark(&grid[[7, 1]]), None);
+        assert_eq!(grid.cell_transform([2, 1]).translation.x, 1.0);
+        assert!(!grid.has_cell_transform([7, 1]));
+
+        // the rest of the cells are swapped
+        grid.swap_regions(rect(0..1, 2..4), [9, 2]);
+        assert_eq!(mark(&grid[[0, 3]]), Some([9, 3]));
+        assert_eq!(mark(&grid[[9, 2]]), Some([0, 2]));
+
+        // sprites reaching into a rect are removed even if every swapped cell is empty
+        let mut grid = SpriteGrid::empty(([10, 4], Vec2::ONE));
+        grid.place([0, 0], [3, 2], marked([0, 0]));
+        grid.swap_regions(rect(1..3, 0..2), [6, 0]);
+        assert!(grid.footprints_are_consistent());
+        assert_eq!(grid.owner([1, 0]), [1, 0]);
+        assert_eq!(grid.multi_cells().count(), 0);
+    }
+}
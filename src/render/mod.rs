@@ -1,7 +1,7 @@
 use crate::prelude::*;
-use bevy::math::vec2;
 use bevy::prelude::*;
 use bevy::render::texture::DEFAULT_IMAGE_HANDLE;
+use bevy::render::view::RenderLayers;
 use bevy::render::Extract;
 use bevy::render::RenderApp;
 use bevy::render::RenderStage;
@@ -9,13 +9,198 @@ use bevy::sprite;
 use bevy::sprite::ExtractedSprite;
 use bevy::sprite::ExtractedSprites;
 use bevy::sprite::SpriteSystem;
+use bevy::tasks::ComputeTaskPool;
+use bevy::utils::HashMap;
 use copyless::VecHelper;
 use std::ops::Mul;
+use std::ops::Range;
 
+/// The sprite for the cell `[x, y]`, `None` if its texture atlas isn't loaded.
+fn extract_cell(
+    entity: Entity,
+    sprite_grid: &SpriteGrid,
+    global_transform: &GlobalTransform,
+    texture_atlases: &Assets<TextureAtlas>,
+    [x, y]: [usize; 2],
+    sprite_cell: &SpriteCell,
+) -> Option<ExtractedSprite> {
+    let (anchor, offset) = match sprite_cell {
+        SpriteCell::Texture(cell) => (cell.anchor.as_vec(), cell.offset),
+        SpriteCell::Color(_) => (Vec2::ZERO, Vec2::ZERO),
+    };
+    // the cell transform is applied about the center of the cell,
+    // then the sprite's anchor point goes on the matching point of the cell
+    let cell_center = sprite_grid
+        .sprite_center([x, y])
+        .extend(sprite_grid.cell_depth([x, y]));
+    let anchor_translation = (anchor * sprite_grid.sprite_size([x, y]) + offset).extend(0.0);
+    let cell_transform = Transform::from_translation(cell_center)
+        .mul_transform(sprite_grid.cell_transform([x, y]))
+        .mul_transform(Transform::from_translation(anchor_translation));
+    let transform = global_transform.mul(cell_transform);
+    let extracted_sprite = match sprite_cell {
+        SpriteCell::Texture(cell) => {
+            let (image_handle_id, rect) = match &cell.texture {
+                TextureSource::Image { handle } => (handle.id, None),
+                TextureSource::Atlas { handle, index } => {
+                    let texture_atlas = texture_atlases.get(handle)?;
+                    (
+                        texture_atlas.texture.id,
+                        Some(texture_atlas.textures[*index].into()),
+                    )
+                }
+            };
+            // multi-cell sprites are stretched over their footprint by default
+            let custom_size = cell.custom_size.or_else(|| {
+                (sprite_grid.footprint([x, y]) != [1, 1]).then(|| sprite_grid.sprite_size([x, y]))
+            });
+            ExtractedSprite {
+                entity,
+                color: cell.color,
+                transform,
+                rect,
+                custom_size,
+                flip_x: cell.flip_x,
+                flip_y: cell.flip_y,
+                image_handle_id,
+                anchor,
+            }
+        }
+        &SpriteCell::Color(color) => ExtractedSprite {
+            entity,
+            color,
+            transform,
+            rect: None,
+            custom_size: Some(sprite_grid.sprite_size([x, y])),
+            flip_x: false,
+            flip_y: false,
+            image_handle_id: DEFAULT_IMAGE_HANDLE.id,
+            anchor: Vec2::ZERO,
+        },
+    };
+    Some(extracted_sprite)
+}
+
+/// Large views are extracted in parallel, in at most one band of rows per thread
+/// with at least this many cells in each band.
+const PARALLEL_EXTRACTION_CELLS: usize = 16 * 1024;
+
+/// Splits the `rows` of a view `width` cells wide into the bands that
+/// `threads` threads extract, bottom to top. Small views are a single band.
+fn bands(rows: Range<usize>, width: usize, threads: usize) -> Vec<Range<usize>> {
+    let bands = threads.min(width * rows.len() / PARALLEL_EXTRACTION_CELLS);
+    if bands < 2 {
+        return vec![rows];
+    }
+    let band_height = (rows.len() + bands - 1) / bands;
+    let top = rows.end;
+    rows.step_by(band_height)
+        .map(|bottom| bottom..(bottom + band_height).min(top))
+        .collect()
+}
+
+/// Extracts the cells in `rows` of `view_rects[i]`
+/// that aren't in any of the earlier view rects.
+#[allow(clippy::too_many_arguments)]
+fn extract_rows(
+    entity: Entity,
+    sprite_grid: &SpriteGrid,
+    global_transform: &GlobalTransform,
+    texture_atlases: &Assets<TextureAtlas>,
+    view_rects: &[SpriteGridRect],
+    i: usize,
+    rows: Range<usize>,
+    sprites: &mut Vec<ExtractedSprite>,
+) {
+    for (cell, sprite_cell) in sprite_grid.iter(view_rects[i].xs(), rows) {
+        // cells seen by more than one camera are only extracted once
+        if view_rects[..i].iter().any(|rect| rect.contains(cell)) {
+            continue;
+        }
+        if let Some(extracted_sprite) = extract_cell(
+            entity,
+            sprite_grid,
+            global_transform,
+            texture_atlases,
+            cell,
+            sprite_cell,
+        ) {
+            sprites.alloc().init(extracted_sprite);
+        }
+    }
+}
+
+/// The sprites extracted from a grid, reused until the grid, its transform,
+/// its visible cells or the texture atlases change.
+#[derive(Default)]
+struct CachedGrid {
+    view_rects: Vec<SpriteGridRect>,
+    sprites: Vec<ExtractedSprite>,
+    seen: bool,
+}
+
+/// What changed since the last frame that can change a grid's sprites.
+#[derive(Clone, Copy, Default)]
+struct GridChanges {
+    grid: bool,
+    transform: bool,
+    atlases: bool,
+}
+
+impl CachedGrid {
+    /// Whether the sprites have to be extracted again to draw the cells in `view_rects`.
+    fn is_stale(&self, changes: GridChanges, view_rects: &[SpriteGridRect]) -> bool {
+        changes.grid || changes.transform || changes.atlases || self.view_rects != view_rects
+    }
+}
+
+#[derive(Default)]
+struct SpriteGridExtractCache {
+    grids: HashMap<Entity, CachedGrid>,
+}
+
+/// Replaces `view_rects` with the cells of the grid in view of each camera,
+/// given as the half size and transform of the camera's view.
+fn visible_rects<'a>(
+    sprite_grid: &SpriteGrid,
+    global_transform: &GlobalTransform,
+    view: Option<&SpriteGridView>,
+    camera_views: impl Iterator<Item = (Vec2, &'a GlobalTransform)>,
+    view_rects: &mut Vec<SpriteGridRect>,
+) {
+    let bounds = view.map_or(sprite_grid.bounds(), |view| view.0);
+    view_rects.clear();
+    if let SpriteGridCulling::Enabled { margin } = sprite_grid.culling {
+        view_rects.extend(
+            camera_views
+                .filter_map(|(half_size, view_transform)| {
+                    pick_rect(
+                        sprite_grid,
+                        global_transform,
+                        half_size + margin,
+                        view_transform,
+                    )
+                })
+                .filter_map(|rect| rect.intersect_with(bounds)),
+        );
+    } else {
+        view_rects.push(bounds);
+    }
+    // multi-cell sprites can reach into view from origins below and to the left
+    let [w, h] = sprite_grid.max_footprint();
+    for view_rect in view_rects {
+        view_rect.left = view_rect.left.saturating_sub(w - 1);
+        view_rect.bottom = view_rect.bottom.saturating_sub(h - 1);
+    }
+}
+
+#[allow(clippy::type_complexity)]
 fn extract_grid_sprites(
-    cameras: Extract<Query<(&OrthographicProjection, &GlobalTransform), With<Camera2d>>>,
+    cameras: Extract<Query<(&Camera, &GlobalTransform, Option<&RenderLayers>), With<Camera2d>>>,
     mut extracted_sprites: ResMut<ExtractedSprites>,
+    mut cache: ResMut<SpriteGridExtractCache>,
     texture_atlases: Extract<Res<Assets<TextureAtlas>>>,
+    mut atlas_events: Extract<EventReader<AssetEvent<TextureAtlas>>>,
     sprite_grid_query: Extract<
         Query<(
             Entity,
@@ -23,108 +208,130 @@ fn extract_grid_sprites(
             &GlobalTransform,
             &ComputedVisibility,
             Option<&SpriteGridView>,
+            Option<&RenderLayers>,
+            ChangeTrackers<SpriteGrid>,
+            ChangeTrackers<GlobalTransform>,
         )>,
     >,
 ) {
-    let (projection, camera_transform) =
-        if let Some((projection, camera_transform)) = cameras.iter().next() {
-            (projection, camera_transform)
-        } else {
-            return;
-        };
-    let culling_rect_half_size = projection.right * Vec2::X + projection.top * Vec2::Y;
+    let atlases_changed = atlas_events.iter().last().is_some();
+    let texture_atlases: &Assets<TextureAtlas> = &texture_atlases;
+    let culling_rects: Vec<_> = cameras
+        .iter()
+        .filter(|(camera, ..)| camera.is_active)
+        .map(|(camera, camera_transform, camera_layers)| {
+            // the corners of the viewport in the camera's local space
+            let view_to_camera = camera.projection_matrix().inverse();
+            let min = view_to_camera.project_point3(Vec3::new(-1.0, -1.0, 0.0));
+            let max = view_to_camera.project_point3(Vec3::new(1.0, 1.0, 0.0));
+            let half_size = 0.5 * (max - min).truncate().abs();
+            let view_transform =
+                camera_transform.mul(Transform::from_translation(0.5 * (min + max)));
+            let camera_layers = camera_layers.copied().unwrap_or_default();
+            (half_size, view_transform, camera_layers)
+        })
+        .collect();
+    if culling_rects.is_empty() {
+        return;
+    }
 
-    for (entity, sprite_grid, global_transform, visibility, view) in sprite_grid_query.iter() {
+    let mut view_rects = Vec::new();
+    for (
+        entity,
+        sprite_grid,
+        global_transform,
+        visibility,
+        view,
+        grid_layers,
+        grid_tracker,
+        transform_tracker,
+    ) in sprite_grid_query.iter()
+    {
         if !visibility.is_visible() {
             continue;
         }
-        if sprite_grid.x_len == 0 || sprite_grid.y_len == 0 {
+        // like sprites, grids are only seen by cameras sharing one of their render layers
+        let grid_layers = grid_layers.copied().unwrap_or_default();
+        let mut seen_by = culling_rects
+            .iter()
+            .filter(|(.., camera_layers)| camera_layers.intersects(&grid_layers))
+            .peekable();
+        if seen_by.peek().is_none() {
             continue;
         }
-        let alignment_translation = -sprite_grid.alignment.0 * sprite_grid.grid_size();
-        let grid_transform = global_transform.mul(Transform {
-            translation: alignment_translation.extend(0.0),
-            ..Default::default()
-        });
-        let view_rect = if let SpriteGridCulling::Enabled { margin } = sprite_grid.culling {
-            if let Some(ranges) = pick_rect(
-                sprite_grid,
-                global_transform,
-                culling_rect_half_size + margin,
-                camera_transform,
-            ) {
-                if let Some(view) = view {
-                    let ranges = ranges.intersect_with(view.0);
-                    if ranges.is_none() {
-                        continue;
-                    }
-                    ranges.unwrap()
-                } else {
-                    ranges
-                }
-            } else {
-                continue;
-            }
-        } else if let Some(view) = view {
-            view.0
-        } else {
-            SpriteGridRect {
-                left: 0,
-                right: sprite_grid.x_len,
-                bottom: 0,
-                top: sprite_grid.y_len,
-            }
+        if sprite_grid.x_len() == 0 || sprite_grid.y_len() == 0 {
+            continue;
+        }
+        // one rect of visible cells for each camera
+        visible_rects(
+            sprite_grid,
+            global_transform,
+            view,
+            seen_by.map(|(half_size, view_transform, _)| (*half_size, view_transform)),
+            &mut view_rects,
+        );
+
+        // the sprites are in world space, so moving a camera only
+        // invalidates the cache once a different set of cells is in view
+        let cached = cache.grids.entry(entity).or_default();
+        cached.seen = true;
+        let changes = GridChanges {
+            grid: grid_tracker.is_changed(),
+            transform: transform_tracker.is_changed(),
+            atlases: atlases_changed,
         };
-        let mut count = 0;
-        for ([x, y], sprite_cell) in sprite_grid.iter(view_rect.xs(), view_rect.ys()) {
-            let grid_pos = (vec2(x as f32, y as f32) + 0.5 * Vec2::ONE) * sprite_grid.cell_size;
-            let cell_transform = Transform {
-                translation: grid_pos.extend(0.0),
-                ..Default::default()
-            };
-            let transform = grid_transform.mul(cell_transform);
-            let extracted_sprite = match sprite_cell {
-                SpriteCell::Texture(cell) => {
-                    let (image_handle_id, rect) = match &cell.texture {
-                        TextureSource::Image { handle } => (handle.id, None),
-                        TextureSource::Atlas { handle, index } => {
-                            if let Some(texture_atlas) = texture_atlases.get(handle) {
-                                (
-                                    texture_atlas.texture.id,
-                                    Some(texture_atlas.textures[*index].into()),
-                                )
-                            } else {
-                                continue;
-                            }
-                        }
-                    };
-                    ExtractedSprite {
+        if cached.is_stale(changes, &view_rects) {
+            cached.sprites.clear();
+            let pool = ComputeTaskPool::get();
+            for (i, view_rect) in view_rects.iter().enumerate() {
+                let bands = bands(view_rect.ys(), view_rect.width(), pool.thread_num());
+                if bands.len() < 2 {
+                    extract_rows(
                         entity,
-                        color: cell.color,
-                        transform,
-                        rect,
-                        custom_size: cell.custom_size,
-                        flip_x: cell.flip_x,
-                        flip_y: cell.flip_y,
-                        image_handle_id,
-                        anchor: Vec2::ZERO,
+                        sprite_grid,
+                        global_transform,
+                        texture_atlases,
+                        &view_rects,
+                        i,
+                        view_rect.ys(),
+                        &mut cached.sprites,
+                    );
+                    continue;
+                }
+                // large views are split into bands of rows extracted in parallel,
+                // then merged back in order
+                let view_rects = &view_rects;
+                let extracted_bands = pool.scope(|scope| {
+                    for rows in bands {
+                        scope.spawn(async move {
+                            let mut sprites = Vec::new();
+                            extract_rows(
+                                entity,
+                                sprite_grid,
+                                global_transform,
+                                texture_atlases,
+                                view_rects,
+                                i,
+                                rows,
+                                &mut sprites,
+                            );
+                            sprites
+                        });
                     }
+                });
+                for mut band in extracted_bands {
+                    cached.sprites.append(&mut band);
                 }
-                &SpriteCell::Color(color) => ExtractedSprite {
-                    entity,
-                    color,
-                    transform,
-                    rect: None,
-                    custom_size: Some(sprite_grid.cell_size),
-                    flip_x: false,
-                    flip_y: false,
-                    image_handle_id: DEFAULT_IMAGE_HANDLE.id,
-                    anchor: Vec2::ZERO,
-                },
-            };
-            extracted_sprites.sprites.alloc().init(extracted_sprite);
+            }
+            cached.view_rects.clone_from(&view_rects);
         }
+        extracted_sprites.sprites.extend_from_slice(&cached.sprites);
     }
+
+    // forget grids that weren't drawn this frame
+    cache
+        .grids
+        .retain(|_, cached| std::mem::take(&mut cached.seen));
 }
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, SystemLabel)]
@@ -137,6 +344,7 @@ pub(crate) struct RenderSpriteGridPlugin;
 impl Plugin for RenderSpriteGridPlugin {
     fn build(&self, app: &mut App) {
         if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.init_resource::<SpriteGridExtractCache>();
             render_app.add_system_to_stage(
                 RenderStage::Extract,
                 extract_grid_sprites
@@ -146,3 +354,104 @@ impl Plugin for RenderSpriteGridPlugin {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::math::vec2;
+
+    fn grid() -> SpriteGrid {
+        SpriteGrid::from_cell(
+            ([100, 80], Vec2::splat(10.0), SpriteGridAlignment::center()),
+            Color::WHITE,
+        )
+    }
+
+    fn rects(grid: &SpriteGrid, camera: Vec2) -> Vec<SpriteGridRect> {
+        let camera = GlobalTransform::from_translation(camera.extend(0.0));
+        let mut view_rects = Vec::new();
+        visible_rects(
+            grid,
+            &GlobalTransform::default(),
+            None,
+            std::iter::once((vec2(200.0, 150.0), &camera)),
+            &mut view_rects,
+        );
+        view_rects
+    }
+
+    #[test]
+    fn cached_sprites_go_stale_with_their_inputs() {
+        let mut grid = grid();
+        let camera = vec2(20.0, -30.0);
+        let view_rects = rects(&grid, camera);
+        let unchanged = GridChanges::default();
+        let mut cached = CachedGrid::default();
+        assert!(cached.is_stale(unchanged, &view_rects));
+        cached.view_rects = view_rects.clone();
+        assert!(!cached.is_stale(unchanged, &view_rects));
+
+        // mutating the grid, moving it or reloading an atlas invalidates the sprites
+        for changes in [
+            GridChanges {
+                grid: true,
+                ..unchanged
+            },
+            GridChanges {
+                transform: true,
+                ..unchanged
+            },
+            GridChanges {
+                atlases: true,
+                ..unchanged
+            },
+        ] {
+            assert!(cached.is_stale(changes, &view_rects));
+        }
+
+        // moving the camera only invalidates them once other cells come into view
+        assert!(!cached.is_stale(unchanged, &rects(&grid, camera + vec2(1.0, 2.0))));
+        assert!(cached.is_stale(unchanged, &rects(&grid, camera + vec2(25.0, 0.0))));
+        assert!(cached.is_stale(unchanged, &rects(&grid, camera - vec2(0.0, 25.0))));
+
+        // a multi-cell sprite makes the rects reach further down and left
+        grid.place([50, 40], [3, 2], Color::RED);
+        let grown = rects(&grid, camera);
+        assert_eq!(grown[0].left + 2, view_rects[0].left);
+        assert_eq!(grown[0].bottom + 1, view_rects[0].bottom);
+        assert!(cached.is_stale(unchanged, &grown));
+
+        // without culling every cell of the grid is in view
+        grid.culling = SpriteGridCulling::Disabled;
+        assert_eq!(rects(&grid, camera), [grid.bounds()]);
+    }
+
+    #[test]
+    fn bands_split_rows_in_order() {
+        for (rows, width, threads, expected) in [
+            (0..10, 10, 8, 1),
+            (3..1003, 200, 8, 8),
+            (0..120, 300, 8, 2),
+            (5..6, 1_000_000, 8, 1),
+            (0..1000, 1000, 1, 1),
+        ] {
+            let bands = bands(rows.clone(), width, threads);
+            assert_eq!(bands.len(), expected, "{rows:?}");
+            let banded: Vec<_> = bands.iter().flat_map(|band| band.clone()).collect();
+            assert_eq!(banded, rows.clone().collect::<Vec<_>>());
+            // every band but the last is worth a thread
+            for band in &bands[..bands.len() - 1] {
+                assert!(PARALLEL_EXTRACTION_CELLS <= band.len() * width);
+            }
+        }
+
+        // extracting the bands one after another visits the cells in serial order
+        let grid = SpriteGrid::from_cell(([300, 120], Vec2::ONE), Color::WHITE);
+        let serial: Vec<_> = grid.iter(0..300, 0..120).map(|(cell, _)| cell).collect();
+        let banded: Vec<_> = bands(0..120, 300, 8)
+            .into_iter()
+            .flat_map(|rows| grid.iter(0..300, rows).map(|(cell, _)| cell))
+            .collect();
+        assert_eq!(banded, serial);
+    }
+}
@@ -0,0 +1,447 @@
+use crate::prelude::*;
+use bevy::prelude::*;
+use std::cmp::max;
+use std::cmp::min;
+
+/// Shape rasterization in cell coordinates.
+///
+/// Shapes can extend past the edges of the grid, only the cells
+/// inside the grid are set.
+impl SpriteGrid {
+    /// Sets `[x, y]` to `cell` if it is inside the grid.
+    fn plot(&mut self, point: [i64; 2], cell: &Option<SpriteCell>) {
+        if let Some(point) = self.offset([0, 0], point) {
+            self.put(point, cell.clone());
+        }
+    }
+
+    /// Sets the cells on the line from `a` to `b`, inclusive.
+    ///
+    /// The line is clipped to the grid before it is rasterized,
+    /// so only the steps inside the grid are visited.
+    pub fn draw_line(&mut self, a: [i64; 2], b: [i64; 2], cell: Option<SpriteCell>) {
+        let lens = [self.x_len() as i64, self.y_len() as i64];
+        let delta = [(b[0] - a[0]).abs(), (b[1] - a[1]).abs()];
+        let step = [
+            if a[0] < b[0] { 1 } else { -1 },
+            if a[1] < b[1] { 1 } else { -1 },
+        ];
+        // Bresenham steps along the longer axis, rounding the other to the nearest cell
+        let major = if delta[1] <= delta[0] { 0 } else { 1 };
+        let minor = 1 - major;
+        let n = delta[major];
+        let offset = |k: i64| {
+            if n == 0 {
+                0
+            } else {
+                (2 * k * delta[minor] + n).div_euclid(2 * n)
+            }
+        };
+        // the steps `t` for which `start + sign * t` is inside `0..len`
+        let inside = |start: i64, sign: i64, len: i64| {
+            if sign == 1 {
+                (-start, len - 1 - start)
+            } else {
+                (start - len + 1, start)
+            }
+        };
+        // clip the steps to the grid, like Liang-Barsky but in whole steps
+        let (mut first, mut last) = inside(a[major], step[major], lens[major]);
+        first = first.max(0);
+        last = last.min(n);
+        let (lowest, highest) = inside(a[minor], step[minor], lens[minor]);
+        if delta[minor] == 0 {
+            if 0 < lowest || highest < 0 {
+                return;
+            }
+        } else {
+            // the first step whose offset is at least `lowest`
+            // and the last step whose offset is at most `highest`
+            let d = 2 * delta[minor];
+            let rounded_up = -(n - 2 * n * lowest).div_euclid(d);
+            first = first.max(rounded_up);
+            last = last.min((2 * n * (highest + 1) - n - 1).div_euclid(d));
+        }
+        for k in first..=last {
+            let mut point = [0; 2];
+            point[major] = a[major] + step[major] * k;
+            point[minor] = a[minor] + step[minor] * offset(k);
+            self.plot(point, &cell);
+        }
+    }
+
+    /// Sets the cells on the border of `rect`.
+    pub fn draw_rect(&mut self, rect: SpriteGridRect, cell: Option<SpriteCell>) {
+        if rect.width() == 0 || rect.height() == 0 {
+            return;
+        }
+        let left = rect.left as i64;
+        let right = rect.right as i64 - 1;
+        let bottom = rect.bottom as i64;
+        let top = rect.top as i64 - 1;
+        self.draw_line([left, bottom], [right, bottom], cell.clone());
+        self.draw_line([left, top], [right, top], cell.clone());
+        self.draw_line([left, bottom], [left, top], cell.clone());
+        self.draw_line([right, bottom], [right, top], cell);
+    }
+
+    /// Sets every cell in `rect`.
+    pub fn fill_rect(&mut self, rect: SpriteGridRect, cell: Option<SpriteCell>) {
+        if let Some(rect) = rect.intersect_with(self.bounds()) {
+            for y in rect.ys() {
+                for x in rect.xs() {
+                    self.plot([x as i64, y as i64], &cell);
+                }
+            }
+        }
+    }
+
+    /// Sets `[x, y]` to `cell` if it is inside the grid, for points far outside of it.
+    fn plot_wide(&mut self, [x, y]: [i128; 2], cell: &Option<SpriteCell>) {
+        if let (Ok(x), Ok(y)) = (i64::try_from(x), i64::try_from(y)) {
+            self.plot([x, y], cell);
+        }
+    }
+
+    /// The distances from `center` to the nearest and furthest of the `len` cells
+    /// along one axis of the grid.
+    fn axis_distances(center: i128, len: usize) -> (i128, i128) {
+        let last = len as i128 - 1;
+        let nearest = if center < 0 {
+            -center
+        } else {
+            (center - last).max(0)
+        };
+        (nearest, center.abs().max((last - center).abs()))
+    }
+
+    /// Sets the cells on the outline of the circle around `center`.
+    ///
+    /// The outline is the midpoint circle, each octant is walked only
+    /// over the rows or columns that are inside the grid.
+    pub fn draw_circle(&mut self, center: [i64; 2], radius: usize, cell: Option<SpriteCell>) {
+        if self.x_len() == 0 || self.y_len() == 0 {
+            return;
+        }
+        let [cx, cy] = [center[0] as i128, center[1] as i128];
+        let r = radius as i128;
+        let (nearest_x, furthest_x) = Self::axis_distances(cx, self.x_len());
+        let (nearest_y, furthest_y) = Self::axis_distances(cy, self.y_len());
+        if r < nearest_x || r < nearest_y {
+            return;
+        }
+        // the octants offset by `dy` rows, then the octants offset by `dy` columns
+        for (nearest, furthest) in [(nearest_y, furthest_y), (nearest_x, furthest_x)] {
+            for dy in nearest..=furthest.min(r) {
+                let dx = circle_x(radius as u128, dy as u128) as i128;
+                if dx < dy {
+                    break;
+                }
+                for [ox, oy] in [
+                    [dx, dy],
+                    [dy, dx],
+                    [-dy, dx],
+                    [-dx, dy],
+                    [-dx, -dy],
+                    [-dy, -dx],
+                    [dy, -dx],
+                    [dx, -dy],
+                ] {
+                    self.plot_wide([cx + ox, cy + oy], &cell);
+                }
+            }
+        }
+    }
+
+    /// Sets every cell within `radius` cells of `center`.
+    /// Only the rows inside the grid are visited.
+    pub fn fill_circle(&mut self, center: [i64; 2], radius: usize, cell: Option<SpriteCell>) {
+        if self.x_len() == 0 || self.y_len() == 0 {
+            return;
+        }
+        let [cx, cy] = [center[0] as i128, center[1] as i128];
+        let r = radius as i128;
+        let bottom = max(cy - r, 0);
+        let top = min(cy + r, self.y_len() as i128 - 1);
+        let r = radius as u128;
+        for y in bottom..=top {
+            let dy = (y - cy).unsigned_abs();
+            let half_width = isqrt(r * r + r - dy * dy) as i128;
+            let left = max(cx - half_width, 0);
+            let right = min(cx + half_width, self.x_len() as i128 - 1);
+            for x in left..=right {
+                self.plot_wide([x, y], &cell);
+            }
+        }
+    }
+
+    /// Sets every cell whose center is inside the polygon, using the even-odd rule.
+    ///
+    /// The polygon's vertices are in cell coordinates, the center of the cell `[x, y]`
+    /// is at `(x + 0.5, y + 0.5)`.
+    pub fn fill_polygon(&mut self, vertices: &[Vec2], cell: Option<SpriteCell>) {
+        if vertices.len() < 3 {
+            return;
+        }
+        let (min_y, max_y) = vertices.iter().fold((f32::MAX, f32::MIN), |(lo, hi), v| {
+            (lo.min(v.y), hi.max(v.y))
+        });
+        let first_row = (min_y - 0.5).ceil().max(0.0) as usize;
+        let last_row = ((max_y - 0.5).ceil().max(0.0) as usize).min(self.y_len());
+        let mut crossings = Vec::new();
+        for y in first_row..last_row {
+            let center_y = y as f32 + 0.5;
+            crossings.clear();
+            for (i, &p) in vertices.iter().enumerate() {
+                let q = vertices[(i + 1) % vertices.len()];
+                if (p.y <= center_y) != (q.y <= center_y) {
+                    crossings.push(p.x + (center_y - p.y) * (q.x - p.x) / (q.y - p.y));
+                }
+            }
+            crossings.sort_by(|a, b| a.total_cmp(b));
+            for span in crossings.chunks_exact(2) {
+                let left = (span[0] - 0.5).ceil().max(0.0) as usize;
+                let right = ((span[1] - 0.5).ceil().max(0.0) as usize).min(self.x_len());
+                for x in left..right {
+                    self.plot([x as i64, y as i64], &cell);
+                }
+            }
+        }
+    }
+}
+
+/// The integer square root of `n`, rounded down.
+fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+    // start just above the root, Newton's method then converges down to it
+    let estimate = (n as f64).sqrt() as u128;
+    let mut x = estimate + (estimate >> 40) + 1;
+    loop {
+        let next = (x + n / x) / 2;
+        if x <= next {
+            return x;
+        }
+        x = next;
+    }
+}
+
+/// The horizontal offset of the midpoint circle of radius `r`, `y` rows above
+/// its center, in the octant where `y <= x`.
+///
+/// The midpoint algorithm picks the largest `x` with `x * (x - 1) < r * r - y * y`.
+fn circle_x(r: u128, y: u128) -> u128 {
+    let m = r * r - y * y;
+    let s = isqrt(m);
+    if s * s + s < m {
+        s + 1
+    } else {
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::utils::HashSet;
+    use std::ops::Range;
+
+    /// How far outside of the grid the shapes reach.
+    const MARGIN: i64 = 10;
+    const SIZE: [usize; 2] = [20, 15];
+
+    fn painted(grid: &SpriteGrid) -> HashSet<[i64; 2]> {
+        grid.iter(0..grid.x_len(), 0..grid.y_len())
+            .map(|([x, y], _)| [x as i64, y as i64])
+            .collect()
+    }
+
+    /// The cells of an unclipped reference raster that are inside the grid.
+    fn clipped(points: impl IntoIterator<Item = [i64; 2]>) -> HashSet<[i64; 2]> {
+        points
+            .into_iter()
+            .filter(|&[x, y]| (0..SIZE[0] as i64).contains(&x) && (0..SIZE[1] as i64).contains(&y))
+            .collect()
+    }
+
+    /// Bresenham's line algorithm, stepping through every point.
+    fn reference_line(a: [i64; 2], b: [i64; 2]) -> Vec<[i64; 2]> {
+        let [dx, dy] = [(b[0] - a[0]).abs(), -(b[1] - a[1]).abs()];
+        let [sx, sy] = [(b[0] - a[0]).signum(), (b[1] - a[1]).signum()];
+        let mut err = dx + dy;
+        let mut point = a;
+        let mut points = vec![point];
+        while point != b {
+            let e2 = 2 * err;
+            if dy <= e2 {
+                err += dy;
+                point[0] += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                point[1] += sy;
+            }
+            points.push(point);
+        }
+        points
+    }
+
+    /// The midpoint circle algorithm, walking every octant.
+    fn reference_circle([cx, cy]: [i64; 2], radius: i64) -> Vec<[i64; 2]> {
+        let mut points = Vec::new();
+        let mut x = radius;
+        let mut y = 0;
+        let mut err = 1 - x;
+        while y <= x {
+            for [dx, dy] in [
+                [x, y],
+                [y, x],
+                [-y, x],
+                [-x, y],
+                [-x, -y],
+                [-y, -x],
+                [y, -x],
+                [x, -y],
+            ] {
+                points.push([cx + dx, cy + dy]);
+            }
+            y += 1;
+            if err < 0 {
+                err += 2 * y + 1;
+            } else {
+                x -= 1;
+                err += 2 * (y - x) + 1;
+            }
+        }
+        points
+    }
+
+    fn reference_disc([cx, cy]: [i64; 2], radius: i64) -> Vec<[i64; 2]> {
+        let mut points = Vec::new();
+        for y in cy - radius..=cy + radius {
+            for x in cx - radius..=cx + radius {
+                let [dx, dy] = [x - cx, y - cy];
+                if dx * dx + dy * dy <= radius * radius + radius {
+                    points.push([x, y]);
+                }
+            }
+        }
+        points
+    }
+
+    fn cell() -> Option<SpriteCell> {
+        Some(SpriteCell::Color(Color::WHITE))
+    }
+
+    fn points_in(xs: Range<i64>, ys: Range<i64>) -> impl Iterator<Item = [i64; 2]> {
+        ys.flat_map(move |y| xs.clone().map(move |x| [x, y]))
+    }
+
+    /// Points from well outside of the grid to well inside it.
+    fn points() -> impl Iterator<Item = [i64; 2]> {
+        let xs = (-MARGIN..SIZE[0] as i64 + MARGIN).step_by(3);
+        let ys = (-MARGIN..SIZE[1] as i64 + MARGIN).step_by(4);
+        ys.flat_map(move |y| xs.clone().map(move |x| [x, y]))
+    }
+
+    #[test]
+    fn lines_match_the_reference_raster() {
+        for a in points() {
+            for b in points().step_by(5) {
+                let mut grid = SpriteGrid::empty((SIZE, Vec2::ONE));
+                grid.draw_line(a, b, cell());
+                assert_eq!(painted(&grid), clipped(reference_line(a, b)), "{a:?} {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn circles_match_the_reference_raster() {
+        for center in points() {
+            for radius in [0, 1, 2, 5, 8, 13, 21] {
+                let mut grid = SpriteGrid::empty((SIZE, Vec2::ONE));
+                grid.draw_circle(center, radius as usize, cell());
+                let expected = clipped(reference_circle(center, radius));
+                assert_eq!(painted(&grid), expected, "{center:?} {radius}");
+
+                let mut grid = SpriteGrid::empty((SIZE, Vec2::ONE));
+                grid.fill_circle(center, radius as usize, cell());
+                let expected = clipped(reference_disc(center, radius));
+                assert_eq!(painted(&grid), expected, "{center:?} {radius}");
+            }
+        }
+    }
+
+    #[test]
+    fn rects_match_the_reference_raster() {
+        let mut grid = SpriteGrid::empty((SIZE, Vec2::ONE));
+        let rect = SpriteGridRect {
+            left: 15,
+            right: 30,
+            bottom: 3,
+            top: 9,
+        };
+        grid.draw_rect(rect, cell());
+        let outline = (15..30)
+            .flat_map(|x| [[x, 3], [x, 8]])
+            .chain((3..9).flat_map(|y| [[15, y], [29, y]]));
+        assert_eq!(painted(&grid), clipped(outline));
+
+        let mut grid = SpriteGrid::empty((SIZE, Vec2::ONE));
+        grid.fill_rect(rect, cell());
+        let area = (3..9).flat_map(|y| (15..30).map(move |x| [x, y]));
+        assert_eq!(painted(&grid), clipped(area));
+    }
+
+    #[test]
+    fn huge_circles_are_clipped() {
+        let mut grid = SpriteGrid::empty((SIZE, Vec2::ONE));
+        grid.fill_circle([5, 5], usize::MAX, cell());
+        assert_eq!(painted(&grid).len(), SIZE[0] * SIZE[1]);
+
+        // the outline of a circle this large passes far outside of the grid
+        let mut grid = SpriteGrid::empty((SIZE, Vec2::ONE));
+        grid.draw_circle([5, 5], usize::MAX, cell());
+        assert!(painted(&grid).is_empty());
+
+        // the top of the circle crosses the bottom row of the grid
+        let radius = 1 << 40;
+        let mut grid = SpriteGrid::empty((SIZE, Vec2::ONE));
+        grid.draw_circle([3, -radius], radius as usize, cell());
+        assert!(painted(&grid).contains(&[3, 0]));
+        assert!(painted(&grid).iter().all(|&[_, y]| y == 0));
+        grid.fill_circle([3, -radius], radius as usize, cell());
+        assert_eq!(painted(&grid).len(), SIZE[0]);
+    }
+
+    #[test]
+    fn polygons_match_the_reference_raster() {
+        // a concave arrow head reaching outside of the grid on two sides
+        let vertices = [
+            Vec2::new(-4.0, 2.5),
+            Vec2::new(12.2, -3.0),
+            Vec2::new(25.0, 7.3),
+            Vec2::new(9.7, 4.0),
+            Vec2::new(14.0, 19.0),
+        ];
+        let inside = |point: Vec2| {
+            let mut inside = false;
+            for (i, &p) in vertices.iter().enumerate() {
+                let q = vertices[(i + 1) % vertices.len()];
+                if (p.y <= point.y) != (q.y <= point.y)
+                    && point.x < p.x + (point.y - p.y) * (q.x - p.x) / (q.y - p.y)
+                {
+                    inside = !inside;
+                }
+            }
+            inside
+        };
+        let mut grid = SpriteGrid::empty((SIZE, Vec2::ONE));
+        grid.fill_polygon(&vertices, cell());
+        assert!(painted(&grid).len() > 100);
+        let area = points_in(-MARGIN..30, -MARGIN..25)
+            .filter(|&[x, y]| inside(Vec2::new(x as f32 + 0.5, y as f32 + 0.5)));
+        assert_eq!(painted(&grid), clipped(area));
+    }
+}
@@ -1,4 +1,5 @@
 use bevy::prelude::*;
+use bevy::sprite::Anchor;
 
 #[derive(Clone, Debug)]
 pub enum TextureSource {
@@ -38,6 +39,12 @@ pub struct TexturedCell {
     pub flip_x: bool,
     pub flip_y: bool,
     pub custom_size: Option<Vec2>,
+    /// The point of the sprite that is placed at the matching point of its cell,
+    /// `BottomCenter` stands the sprite on the bottom edge of the cell.
+    pub anchor: Anchor,
+    /// Moves the sprite away from its anchor point in the grid's local space.
+    /// Doesn't change which cell is picked at a point.
+    pub offset: Vec2,
 }
 
 impl TexturedCell {
@@ -1,13 +1,22 @@
 use crate::prelude::*;
-use bevy::math::vec2;
+use crate::storage::CellStorage;
 use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::cmp::min;
+use std::cmp::Ordering;
 use std::ops::Index;
 use std::ops::IndexMut;
 use std::ops::Range;
 
+/// Whether cells outside of every camera's view are skipped when extracting.
 #[derive(Copy, Clone, Debug)]
 pub enum SpriteGridCulling {
-    Enabled { margin: Vec2 },
+    /// Cells are culled against each camera's view grown by `margin` world units,
+    /// so that sprites reaching outside of their cells don't pop in and out
+    /// at the edges of the view. New grids use a margin of one and a half cells.
+    Enabled {
+        margin: Vec2,
+    },
     Disabled,
 }
 
@@ -19,6 +28,25 @@ impl Default for SpriteGridCulling {
     }
 }
 
+/// Determines the z offsets of a grid's cells, relative to the grid,
+/// so overlapping sprites are drawn in the right order.
+#[derive(Copy, Clone, Debug)]
+pub enum SpriteGridSorting {
+    /// No offsets, except that isometric cells are drawn back to front.
+    None,
+    /// Cells lower down the grid are drawn over the cells above them,
+    /// with offsets in `[0, 1)`.
+    ByRow,
+    /// The offset of each cell is given by the key function.
+    ByKey(fn([usize; 2], &SpriteCell) -> f32),
+}
+
+impl Default for SpriteGridSorting {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
 /// Determines the alignment of the
 /// sprite grid in relation to it's global transform
 /// and it's point of rotation.
@@ -59,6 +87,7 @@ pub struct SpriteGridGeometry {
     grid_size: [usize; 2],
     cell_size: Vec2,
     alignment: SpriteGridAlignment,
+    layout: SpriteGridLayout,
 }
 
 impl From<([usize; 2], Vec2)> for SpriteGridGeometry {
@@ -67,6 +96,7 @@ impl From<([usize; 2], Vec2)> for SpriteGridGeometry {
             grid_size,
             cell_size,
             alignment: SpriteGridAlignment::default(),
+            layout: SpriteGridLayout::default(),
         }
     }
 }
@@ -77,42 +107,104 @@ impl From<([usize; 2], Vec2, SpriteGridAlignment)> for SpriteGridGeometry {
             grid_size,
             cell_size,
             alignment,
+            layout: SpriteGridLayout::default(),
+        }
+    }
+}
+
+impl From<([usize; 2], Vec2, SpriteGridAlignment, SpriteGridLayout)> for SpriteGridGeometry {
+    fn from(
+        (grid_size, cell_size, alignment, layout): (
+            [usize; 2],
+            Vec2,
+            SpriteGridAlignment,
+            SpriteGridLayout,
+        ),
+    ) -> Self {
+        Self {
+            grid_size,
+            cell_size,
+            alignment,
+            layout,
         }
     }
 }
 
+/// A grid of sprite cells.
+///
+/// By default the cells are stored in a single contiguous
+/// row-major buffer. Large sparse grids can use chunked storage
+/// instead, see [`SpriteGridStorage`].
+///
+/// The storage is a ring buffer, so [`SpriteGrid::shift`] can scroll
+/// the grid without moving any cells.
+///
+/// Cells are laid out in rows and columns by default,
+/// see [`SpriteGridLayout`] for hexagonal and isometric grids.
 #[derive(Clone, Default, Component)]
 pub struct SpriteGrid {
-    pub sprite_cells: Vec<Vec<Option<SpriteCell>>>,
-    pub cell_transforms: Vec<Vec<Transform>>,
+    sprite_cells: CellStorage,
+    /// only cells with a transform have an entry
+    ///
+    /// The overlay maps are keyed by storage position,
+    /// so shifting the grid doesn't have to rebuild them.
+    cell_transforms: HashMap<[usize; 2], Transform>,
+    /// size of each multi-cell sprite, keyed by its origin cell
+    footprints: HashMap<[usize; 2], [usize; 2]>,
+    /// storage position of the origin of the multi-cell sprite covering each occupied cell
+    occupied: HashMap<[usize; 2], [usize; 2]>,
     pub alignment: SpriteGridAlignment,
-    pub x_len: usize,
-    pub y_len: usize,
+    x_len: usize,
+    y_len: usize,
+    /// storage position of the cell `[0, 0]`
+    origin: [usize; 2],
+    /// number of cells the grid has been shifted by
+    scroll: [i64; 2],
+    /// translation of the cells in local space that keeps them in place when the grid is resized
+    cell_offset: Vec2,
     pub cell_size: Vec2,
+    pub layout: SpriteGridLayout,
     pub culling: SpriteGridCulling,
+    pub sorting: SpriteGridSorting,
 }
 
 impl SpriteGrid {
-    pub fn empty(geometry: impl Into<SpriteGridGeometry>) -> Self {
-        let geometry = geometry.into();
+    fn with_cells(geometry: SpriteGridGeometry, sprite_cells: CellStorage) -> Self {
         let [x_len, y_len] = geometry.grid_size;
-        let cells: Vec<Vec<Option<SpriteCell>>> = (0..x_len).map(|_| vec![None; y_len]).collect();
-        let cell_transforms = (0..x_len)
-            .map(|_| vec![Transform::default(); y_len])
-            .collect();
         Self {
-            sprite_cells: cells,
+            sprite_cells,
+            cell_transforms: HashMap::default(),
+            footprints: HashMap::default(),
+            occupied: HashMap::default(),
             alignment: geometry.alignment,
-            cell_transforms,
             x_len,
             y_len,
+            origin: [0, 0],
+            scroll: [0, 0],
+            cell_offset: Vec2::ZERO,
             cell_size: geometry.cell_size,
+            layout: geometry.layout,
             culling: SpriteGridCulling::Enabled {
                 margin: 1.5 * geometry.cell_size,
             },
+            sorting: SpriteGridSorting::default(),
         }
     }
 
+    pub fn empty(geometry: impl Into<SpriteGridGeometry>) -> Self {
+        let geometry = geometry.into();
+        let cells = CellStorage::empty(SpriteGridStorage::Dense, geometry.grid_size);
+        Self::with_cells(geometry, cells)
+    }
+
+    /// An empty grid with chunked storage.
+    /// Only the chunks that are written to are allocated.
+    pub fn empty_chunked(geometry: impl Into<SpriteGridGeometry>) -> Self {
+        let geometry = geometry.into();
+        let cells = CellStorage::chunked(geometry.grid_size);
+        Self::with_cells(geometry, cells)
+    }
+
     pub fn from_cell(
         geometry: impl Into<SpriteGridGeometry>,
         sprite_cell: impl Into<SpriteCell>,
@@ -120,25 +212,11 @@ impl SpriteGrid {
         let geometry = geometry.into();
         let [x_len, y_len] = geometry.grid_size;
         let cell = sprite_cell.into();
-        let cells: Vec<Vec<Option<SpriteCell>>> = (0..x_len)
-            .map(|_| vec![Some(cell.clone()); y_len])
-            .collect();
-        let cell_transforms = (0..x_len)
-            .map(|_| vec![Transform::default(); y_len])
-            .collect();
-        Self {
-            sprite_cells: cells,
-            alignment: geometry.alignment,
-            cell_transforms,
-            x_len,
-            y_len,
-            cell_size: geometry.cell_size,
-            culling: SpriteGridCulling::Enabled {
-                margin: 1.5 * geometry.cell_size,
-            },
-        }
+        let cells = CellStorage::dense(geometry.grid_size, vec![Some(cell); x_len * y_len]);
+        Self::with_cells(geometry, cells)
     }
 
+    /// `c` is called once for each cell, in row-major order.
     pub fn from_fn<I>(
         geometry: impl Into<SpriteGridGeometry>,
         mut c: impl FnMut([usize; 2]) -> Option<I>,
@@ -148,57 +226,1265 @@ impl SpriteGrid {
     {
         let geometry = geometry.into();
         let [x_len, y_len] = geometry.grid_size;
-        let cells: Vec<Vec<Option<SpriteCell>>> = (0..x_len)
-            .map(|x| (0..y_len).map(|y| c([x, y]).map(|s| s.into())).collect())
-            .collect();
-        let cell_transforms = (0..x_len)
-            .map(|_| vec![Transform::default(); y_len])
+        let cells = (0..y_len)
+            .flat_map(|y| (0..x_len).map(move |x| [x, y]))
+            .map(|cell| c(cell).map(|s| s.into()))
             .collect();
-        Self {
-            sprite_cells: cells,
-            alignment: geometry.alignment,
-            cell_transforms,
-            x_len,
-            y_len,
-            cell_size: geometry.cell_size,
-            culling: SpriteGridCulling::Enabled {
-                margin: 1.5 * geometry.cell_size,
-            },
+        let cells = CellStorage::dense(geometry.grid_size, cells);
+        Self::with_cells(geometry, cells)
+    }
+
+    /// number of columns
+    pub fn x_len(&self) -> usize {
+        self.x_len
+    }
+
+    /// number of rows
+    pub fn y_len(&self) -> usize {
+        self.y_len
+    }
+
+    /// The rect covering every cell in the grid.
+    pub fn bounds(&self) -> SpriteGridRect {
+        SpriteGridRect {
+            left: 0,
+            right: self.x_len,
+            bottom: 0,
+            top: self.y_len,
         }
     }
 
+    /// Size of the grid's bounding box in its local space.
     pub fn grid_size(&self) -> Vec2 {
-        vec2(self.x_len as f32, self.y_len as f32) * self.cell_size
+        self.layout.size([self.x_len, self.y_len], self.cell_size)
+    }
+
+    /// Number of cells the grid has been shifted by since it was created,
+    /// see [`SpriteGrid::shift`].
+    pub fn scroll(&self) -> [i64; 2] {
+        self.scroll
     }
 
-    pub fn set(&mut self, [x, y]: [usize; 2], cell: impl Into<SpriteCell>) {
-        self.sprite_cells[x][y] = Some(cell.into());
+    /// Moves the grid's cells to `scroll` cells from the grid's position.
+    pub fn set_scroll(&mut self, scroll: [i64; 2]) {
+        self.scroll = scroll;
+    }
+
+    /// Position of the center of the cell `[x, y]` in the grid's local space,
+    /// for coordinates that may be outside of the grid.
+    pub(crate) fn center_at(&self, [x, y]: [i64; 2]) -> Vec2 {
+        let cell = [x + self.scroll[0], y + self.scroll[1]];
+        self.layout
+            .center(cell, [self.x_len, self.y_len], self.cell_size)
+            - self.alignment.0 * self.grid_size()
+            + self.cell_offset
+    }
+
+    /// Position of the center of the cell `[x, y]` in the grid's local space.
+    pub fn cell_center(&self, [x, y]: [usize; 2]) -> Vec2 {
+        self.center_at([x as i64, y as i64])
+    }
+
+    /// Z offset of the cell `[x, y]` relative to the grid, see [`SpriteGridSorting`].
+    pub fn cell_depth(&self, [x, y]: [usize; 2]) -> f32 {
+        let size = [self.x_len, self.y_len];
+        match self.sorting {
+            SpriteGridSorting::None => self.layout.depth([x, y], size),
+            SpriteGridSorting::ByRow => {
+                let center = self
+                    .layout
+                    .center([x as i64, y as i64], size, self.cell_size);
+                1.0 - center.y / self.grid_size().y
+            }
+            SpriteGridSorting::ByKey(key) => {
+                self[[x, y]].as_ref().map_or(0.0, |cell| key([x, y], cell))
+            }
+        }
+    }
+
+    /// The vertices of the cell `[x, y]` in the grid's local space, counter-clockwise.
+    pub fn cell_corners(&self, cell: [usize; 2]) -> Vec<Vec2> {
+        let center = self.cell_center(cell);
+        let mut corners = self.layout.corners(self.cell_size);
+        for corner in &mut corners {
+            *corner += center;
+        }
+        corners
+    }
+
+    /// The cell containing `point`, which is in the grid's local space.
+    /// The result is not bounds checked.
+    pub fn local_to_cell(&self, point: Vec2) -> [i64; 2] {
+        let point = point - self.cell_offset + self.alignment.0 * self.grid_size();
+        let [x, y] = self
+            .layout
+            .cell_at(point, [self.x_len, self.y_len], self.cell_size);
+        [x - self.scroll[0], y - self.scroll[1]]
+    }
+
+    /// Axial coordinates of `cell`, `None` if the grid's layout isn't hexagonal.
+    ///
+    /// Axial coordinates don't change when the grid is shifted.
+    pub fn cell_to_axial(&self, [x, y]: [usize; 2]) -> Option<[i64; 2]> {
+        match self.layout {
+            SpriteGridLayout::Hex(hex) => {
+                Some(hex.offset_to_axial([x as i64 + self.scroll[0], y as i64 + self.scroll[1]]))
+            }
+            _ => None,
+        }
+    }
+
+    /// The cell at the axial coordinates `axial`, `None` if it is outside of the grid
+    /// or the grid's layout isn't hexagonal.
+    pub fn axial_to_cell(&self, axial: [i64; 2]) -> Option<[usize; 2]> {
+        match self.layout {
+            SpriteGridLayout::Hex(hex) => {
+                let [x, y] = hex.axial_to_offset(axial);
+                self.offset([0, 0], [x - self.scroll[0], y - self.scroll[1]])
+            }
+            _ => None,
+        }
+    }
+
+    pub fn storage(&self) -> SpriteGridStorage {
+        self.sprite_cells.kind()
+    }
+
+    /// Moves the cells into the given kind of storage.
+    pub fn set_storage(&mut self, storage: SpriteGridStorage) {
+        if storage == self.storage() {
+            return;
+        }
+        let size = [self.x_len, self.y_len];
+        let mut cells = CellStorage::empty(storage, size);
+        for y in 0..self.y_len {
+            for x in 0..self.x_len {
+                // `get` first so the empty chunks of the old storage aren't allocated
+                if self.sprite_cells.get([x, y]).is_some() {
+                    *cells.get_mut([x, y]) = self.sprite_cells.get_mut([x, y]).take();
+                }
+            }
+        }
+        self.sprite_cells = cells;
+    }
+
+    /// Frees the chunks of a chunked grid that no longer hold any cells.
+    /// Does nothing for dense grids.
+    pub fn release_empty_chunks(&mut self) {
+        self.sprite_cells.release_empty_chunks();
+    }
+
+    /// Number of chunks currently allocated, `None` if the grid is dense.
+    pub fn allocated_chunks(&self) -> Option<usize> {
+        self.sprite_cells.allocated_chunks()
+    }
+
+    /// storage position of the cell `[x, y]`
+    fn physical(&self, [x, y]: [usize; 2]) -> [usize; 2] {
+        let [ox, oy] = self.origin;
+        let x = x + ox;
+        let y = y + oy;
+        [
+            if x < self.x_len { x } else { x - self.x_len },
+            if y < self.y_len { y } else { y - self.y_len },
+        ]
+    }
+
+    /// the cell at the storage position `[x, y]`
+    fn logical(&self, [x, y]: [usize; 2]) -> [usize; 2] {
+        let [ox, oy] = self.origin;
+        [
+            if ox <= x { x - ox } else { x + self.x_len - ox },
+            if oy <= y { y - oy } else { y + self.y_len - oy },
+        ]
+    }
+
+    /// Like `IndexMut` but doesn't allocate chunks to store `None`,
+    /// and removes the multi-cell sprite whose origin is overwritten.
+    /// Writing a sprite to an occupied cell removes the sprite occupying it.
+    pub(crate) fn put(&mut self, cell: [usize; 2], value: Option<SpriteCell>) {
+        let physical = self.physical(cell);
+        if value.is_some() {
+            self.remove_occupant(physical);
+        }
+        self.remove_footprint(physical);
+        if value.is_some() || self.sprite_cells.get(physical).is_some() {
+            *self.sprite_cells.get_mut(physical) = value;
+        }
+    }
+
+    fn check_bounds(&self, [x, y]: [usize; 2]) {
+        assert!(
+            x < self.x_len && y < self.y_len,
+            "cell [{x}, {y}] is outside of the {}x{} sprite grid",
+            self.x_len,
+            self.y_len
+        );
+    }
+
+    /// The transform of the cell `[x, y]`, relative to the center of the cell.
+    /// Identity for cells that haven't been given a transform.
+    pub fn cell_transform(&self, cell: [usize; 2]) -> Transform {
+        self.check_bounds(cell);
+        self.cell_transforms
+            .get(&self.physical(cell))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The transform of the cell `[x, y]`, relative to the center of the cell.
+    ///
+    /// Cell transforms are stored sparsely, this inserts an identity
+    /// transform for the cell if it doesn't have one yet.
+    pub fn cell_transform_mut(&mut self, cell: [usize; 2]) -> &mut Transform {
+        self.check_bounds(cell);
+        let physical = self.physical(cell);
+        self.cell_transforms.entry(physical).or_default()
+    }
+
+    /// Whether the cell has been given a transform.
+    pub fn has_cell_transform(&self, cell: [usize; 2]) -> bool {
+        self.bounds().contains(cell) && self.cell_transforms.contains_key(&self.physical(cell))
+    }
+
+    pub fn set_cell_transform(&mut self, cell: [usize; 2], transform: Transform) {
+        *self.cell_transform_mut(cell) = transform;
+    }
+
+    /// Resets the cell's transform to the identity and frees its memory.
+    pub fn remove_cell_transform(&mut self, cell: [usize; 2]) -> Option<Transform> {
+        if !self.bounds().contains(cell) {
+            return None;
+        }
+        let physical = self.physical(cell);
+        self.cell_transforms.remove(&physical)
+    }
+
+    /// Resets every cell transform and frees their memory.
+    pub fn clear_cell_transforms(&mut self) {
+        self.cell_transforms = HashMap::default();
+    }
+
+    /// The cells that have been given a transform, in no particular order.
+    pub fn cell_transforms(&self) -> impl Iterator<Item = ([usize; 2], &Transform)> {
+        self.cell_transforms
+            .iter()
+            .map(|(&physical, transform)| (self.logical(physical), transform))
+    }
+
+    /// Places a sprite covering the `[w, h]` cells above and to the right of `origin`.
+    ///
+    /// The origin cell holds the sprite, the other cells are emptied and marked
+    /// as occupied by it. Multi-cell sprites overlapping the area are removed,
+    /// as is the sprite when another sprite is written to one of its occupied cells.
+    /// Multi-cell sprites cut by shifting or resizing, or by inserting or removing
+    /// a row or column, are removed as well.
+    pub fn place(
+        &mut self,
+        origin: [usize; 2],
+        footprint: [usize; 2],
+        sprite_cell: impl Into<SpriteCell>,
+    ) {
+        let [x, y] = origin;
+        let [w, h] = footprint;
+        let area = SpriteGridRect {
+            left: x,
+            right: x + w,
+            bottom: y,
+            top: y + h,
+        };
+        assert!(
+            0 < w && 0 < h && self.bounds().intersect_with(area) == Some(area),
+            "footprint {footprint:?} at {origin:?} is outside of the {}x{} sprite grid",
+            self.x_len,
+            self.y_len
+        );
+        let physical_origin = self.physical(origin);
+        for y in area.ys() {
+            for x in area.xs() {
+                self.remove_multi_cell([x, y]);
+                self.put([x, y], None);
+                if [x, y] != origin {
+                    let physical = self.physical([x, y]);
+                    self.occupied.insert(physical, physical_origin);
+                }
+            }
+        }
+        *self.sprite_cells.get_mut(physical_origin) = Some(sprite_cell.into());
+        if footprint != [1, 1] {
+            self.footprints.insert(physical_origin, footprint);
+        }
+    }
+
+    /// Removes the multi-cell sprite covering `cell` and returns it,
+    /// `None` if `cell` isn't covered by one.
+    pub fn remove_multi_cell(&mut self, cell: [usize; 2]) -> Option<SpriteCell> {
+        let physical = self.physical(cell);
+        let origin = self.occupied.get(&physical).copied().unwrap_or(physical);
+        if !self.remove_footprint(origin) {
+            return None;
+        }
+        self.sprite_cells.get_mut(origin).take()
+    }
+
+    /// Removes the multi-cell sprite occupying the storage position `physical`,
+    /// if `physical` is one of its cells other than the origin.
+    fn remove_occupant(&mut self, physical: [usize; 2]) {
+        if let Some(&origin) = self.occupied.get(&physical) {
+            if self.remove_footprint(origin) && self.sprite_cells.get(origin).is_some() {
+                *self.sprite_cells.get_mut(origin) = None;
+            }
+        }
+    }
+
+    /// Removes the footprint of the multi-cell sprite whose origin is at the storage
+    /// position `origin` and frees the cells it occupies, without touching the sprite.
+    /// Returns false if there is no multi-cell sprite at `origin`.
+    fn remove_footprint(&mut self, origin: [usize; 2]) -> bool {
+        if self.footprints.is_empty() {
+            return false;
+        }
+        let [w, h] = match self.footprints.remove(&origin) {
+            Some(footprint) => footprint,
+            None => return false,
+        };
+        // the occupied cells can wrap around the edges of the storage
+        let [x, y] = origin;
+        for oy in 0..min(h, self.y_len) {
+            for ox in 0..min(w, self.x_len) {
+                let cell = [(x + ox) % self.x_len, (y + oy) % self.y_len];
+                if self.occupied.get(&cell) == Some(&origin) {
+                    self.occupied.remove(&cell);
+                }
+            }
+        }
+        true
+    }
+
+    /// The footprint at the storage position `origin`, ignoring
+    /// multi-cell sprites whose origin has been emptied.
+    fn live_footprint(&self, origin: [usize; 2]) -> Option<[usize; 2]> {
+        let footprint = self.footprints.get(&origin)?;
+        self.sprite_cells
+            .get(origin)
+            .is_some()
+            .then_some(*footprint)
+    }
+
+    /// The size in cells of the sprite at `cell`, `[1, 1]` unless it is
+    /// the origin of a multi-cell sprite.
+    pub fn footprint(&self, cell: [usize; 2]) -> [usize; 2] {
+        self.live_footprint(self.physical(cell)).unwrap_or([1, 1])
+    }
+
+    /// The origin of the multi-cell sprite covering `cell`, or `cell` itself.
+    pub fn owner(&self, cell: [usize; 2]) -> [usize; 2] {
+        match self.occupied.get(&self.physical(cell)) {
+            Some(&origin) if self.live_footprint(origin).is_some() => self.logical(origin),
+            _ => cell,
+        }
+    }
+
+    /// Whether `cell` is covered by a multi-cell sprite whose origin is another cell.
+    pub fn is_occupied(&self, cell: [usize; 2]) -> bool {
+        self.owner(cell) != cell
+    }
+
+    /// The origins and footprints of the multi-cell sprites, in no particular order.
+    pub fn multi_cells(&self) -> impl Iterator<Item = ([usize; 2], [usize; 2])> + '_ {
+        self.footprints.keys().filter_map(|&origin| {
+            self.live_footprint(origin)
+                .map(|footprint| (self.logical(origin), footprint))
+        })
+    }
+
+    /// The largest width and height of any multi-cell sprite, `[1, 1]` if there are none.
+    pub fn max_footprint(&self) -> [usize; 2] {
+        self.multi_cells()
+            .fold([1, 1], |[w, h], (_, [fw, fh])| [w.max(fw), h.max(fh)])
+    }
+
+    /// Center of the area covered by the sprite at `cell`, in the grid's local space.
+    pub fn sprite_center(&self, cell: [usize; 2]) -> Vec2 {
+        let [x, y] = cell;
+        let [w, h] = self.footprint(cell);
+        let last = [(x + w - 1) as i64, (y + h - 1) as i64];
+        0.5 * (self.cell_center(cell) + self.center_at(last))
+    }
+
+    /// Size of the area covered by the sprite at `cell`.
+    pub fn sprite_size(&self, cell: [usize; 2]) -> Vec2 {
+        let [w, h] = self.footprint(cell);
+        Vec2::new(w as f32, h as f32) * self.cell_size
+    }
+
+    /// Whether every multi-cell sprite is inside the grid, and the other cells it covers
+    /// are empty and marked as occupied by it. Checked by `debug_assert` after bulk edits.
+    pub(crate) fn footprints_are_consistent(&self) -> bool {
+        let covers = |origin: [usize; 2], cell: [usize; 2]| {
+            self.live_footprint(origin).map(|[w, h]| {
+                let [x, y] = self.logical(origin);
+                let [cx, cy] = self.logical(cell);
+                (x..x + w).contains(&cx) && (y..y + h).contains(&cy)
+            })
+        };
+        let inside = self.footprints.keys().all(|&origin| {
+            self.live_footprint(origin).map_or(true, |[w, h]| {
+                let [x, y] = self.logical(origin);
+                x + w <= self.x_len
+                    && y + h <= self.y_len
+                    && (y..y + h).all(|cy| {
+                        (x..x + w).all(|cx| {
+                            let physical = self.physical([cx, cy]);
+                            physical == origin
+                                || (self.occupied.get(&physical) == Some(&origin)
+                                    && self.sprite_cells.get(physical).is_none())
+                        })
+                    })
+            })
+        });
+        inside
+            && self
+                .occupied
+                .iter()
+                .all(|(&cell, &origin)| covers(origin, cell) != Some(false))
+    }
+
+    /// Marks the cells under each footprint as occupied.
+    /// Footprints whose origin has been emptied are dropped.
+    /// Only called once the storage is contiguous.
+    fn reindex_occupied(&mut self) {
+        debug_assert_eq!(self.origin, [0, 0]);
+        let sprite_cells = &self.sprite_cells;
+        self.footprints
+            .retain(|&origin, _| sprite_cells.get(origin).is_some());
+        self.occupied.clear();
+        for (&[x, y], &[w, h]) in &self.footprints {
+            for oy in y..y + h {
+                for ox in x..x + w {
+                    if [ox, oy] != [x, y] {
+                        self.occupied.insert([ox, oy], [x, y]);
+                    }
+                }
+            }
+        }
+        debug_assert!(self.footprints_are_consistent());
+    }
+
+    /// Replaces the sprite at `cell`, removing the multi-cell sprite
+    /// if `cell` is the origin of one.
+    pub fn set(&mut self, cell: [usize; 2], sprite_cell: impl Into<SpriteCell>) {
+        self.check_bounds(cell);
+        self.put(cell, Some(sprite_cell.into()));
+    }
+
+    /// all of the cells in row-major order
+    ///
+    /// `None` if the grid is chunked, or if [`SpriteGrid::shift`] has wrapped its
+    /// storage around. The storage is contiguous again after a shift by a multiple
+    /// of the grid's size, and after [`SpriteGrid::make_contiguous`], resizing or
+    /// inserting or removing a row or column.
+    pub fn cells(&self) -> Option<&[Option<SpriteCell>]> {
+        if self.origin != [0, 0] {
+            return None;
+        }
+        self.sprite_cells.as_slice()
+    }
+
+    /// all of the cells in row-major order
+    ///
+    /// `None` if the grid is chunked, or if [`SpriteGrid::shift`] has wrapped its
+    /// storage around. The storage is contiguous again after a shift by a multiple
+    /// of the grid's size, and after [`SpriteGrid::make_contiguous`], resizing or
+    /// inserting or removing a row or column.
+    /// Cells occupied by multi-cell sprites must be left empty, see [`SpriteGrid::place`].
+    pub fn cells_mut(&mut self) -> Option<&mut [Option<SpriteCell>]> {
+        if self.origin != [0, 0] {
+            return None;
+        }
+        self.sprite_cells.as_mut_slice()
+    }
+
+    /// the cells of row `y`, ordered by `x`
+    ///
+    /// `None` if the grid is chunked, or if a horizontal shift has wrapped its
+    /// storage around, see [`SpriteGrid::cells`].
+    pub fn row(&self, y: usize) -> Option<&[Option<SpriteCell>]> {
+        assert!(y < self.y_len, "row {y} is outside of the sprite grid");
+        if self.origin[0] != 0 {
+            return None;
+        }
+        let [_, y] = self.physical([0, y]);
+        let x_len = self.x_len;
+        self.sprite_cells
+            .as_slice()
+            .map(|cells| &cells[y * x_len..(y + 1) * x_len])
+    }
+
+    /// the cells of row `y`, ordered by `x`
+    ///
+    /// `None` if the grid is chunked, or if a horizontal shift has wrapped its
+    /// storage around, see [`SpriteGrid::cells`].
+    /// Cells occupied by multi-cell sprites must be left empty, see [`SpriteGrid::place`].
+    pub fn row_mut(&mut self, y: usize) -> Option<&mut [Option<SpriteCell>]> {
+        assert!(y < self.y_len, "row {y} is outside of the sprite grid");
+        if self.origin[0] != 0 {
+            return None;
+        }
+        let [_, y] = self.physical([0, y]);
+        let x_len = self.x_len;
+        self.sprite_cells
+            .as_mut_slice()
+            .map(|cells| &mut cells[y * x_len..(y + 1) * x_len])
+    }
+
+    /// the cells of column `x`, ordered by `y`
+    ///
+    /// Columns aren't contiguous in memory, so this is a strided iterator
+    /// rather than a slice.
+    pub fn column(&self, x: usize) -> impl Iterator<Item = &Option<SpriteCell>> {
+        assert!(x < self.x_len, "column {x} is outside of the sprite grid");
+        (0..self.y_len).map(move |y| self.sprite_cells.get(self.physical([x, y])))
+    }
+
+    /// the cells of column `x`, ordered by `y`
+    ///
+    /// Allocates every chunk in the column if the grid is chunked.
+    /// Cells occupied by multi-cell sprites must be left empty, see [`SpriteGrid::place`].
+    pub fn column_mut(&mut self, x: usize) -> impl Iterator<Item = &mut Option<SpriteCell>> {
+        assert!(x < self.x_len, "column {x} is outside of the sprite grid");
+        let [x, y_origin] = self.physical([x, 0]);
+        self.sprite_cells.column_mut(x, y_origin)
+    }
+
+    /// Scrolls the grid by `[dx, dy]` cells.
+    ///
+    /// The cell at `[x, y]` moves to `[x - dx, y - dy]`, cells shifted
+    /// off the grid are dropped and the cells exposed on the opposite
+    /// side are set to `fill`. The cells keep their positions in the world,
+    /// it's the grid that moves along by `[dx, dy]` cells. Multi-cell sprites
+    /// that are partly shifted off the grid are removed.
+    ///
+    /// The grid's storage is a ring buffer, so this only touches the exposed
+    /// cells and their overlays. Shifting one column costs `O(y_len)`.
+    pub fn shift(&mut self, [dx, dy]: [i64; 2], fill: Option<SpriteCell>) {
+        self.scroll[0] += dx;
+        self.scroll[1] += dy;
+        if self.x_len == 0 || self.y_len == 0 {
+            return;
+        }
+        let x_len = self.x_len as i64;
+        let y_len = self.y_len as i64;
+        self.origin = [
+            (self.origin[0] as i64 + dx).rem_euclid(x_len) as usize,
+            (self.origin[1] as i64 + dy).rem_euclid(y_len) as usize,
+        ];
+        let exposed = |delta: i64, len: i64| {
+            if 0 <= delta {
+                (len - delta).max(0) as usize..len as usize
+            } else {
+                0..(-delta).min(len) as usize
+            }
+        };
+        let columns = exposed(dx, x_len);
+        let rows = exposed(dy, y_len);
+        for x in columns.clone() {
+            for y in 0..self.y_len {
+                self.expose([x, y], fill.clone());
+            }
+        }
+        for y in rows {
+            for x in 0..self.x_len {
+                if !columns.contains(&x) {
+                    self.expose([x, y], fill.clone());
+                }
+            }
+        }
+        debug_assert!(self.footprints_are_consistent());
+    }
+
+    /// Resets a cell exposed by a shift to `fill`, clearing its overlays.
+    /// The cell was shifted off the grid, so the multi-cell sprite covering it is removed.
+    fn expose(&mut self, cell: [usize; 2], fill: Option<SpriteCell>) {
+        let physical = self.physical(cell);
+        self.cell_transforms.remove(&physical);
+        self.remove_occupant(physical);
+        self.put(cell, fill);
+    }
+
+    /// Rearranges the storage of a shifted grid so that `cells` and `row`
+    /// return slices again. Doesn't change the grid's scroll.
+    pub fn make_contiguous(&mut self) {
+        if self.origin == [0, 0] {
+            return;
+        }
+        let columns = (0..self.x_len).map(Some).collect();
+        let rows = (0..self.y_len).map(Some).collect();
+        self.rebuild(columns, rows, [0, 0], None);
+    }
+
+    /// Rebuilds the grid with `columns.len() * rows.len()` cells.
+    /// `columns[x]` and `rows[y]` give the old column and row that the new
+    /// cell `[x, y]` is moved from, new cells without one are set to `fill`.
+    ///
+    /// The old cell `[0, 0]` stays where it is in the world and becomes the cell `delta`,
+    /// the grid's scroll and cell offset absorb the change of size.
+    fn rebuild(
+        &mut self,
+        columns: Vec<Option<usize>>,
+        rows: Vec<Option<usize>>,
+        delta: [i64; 2],
+        fill: Option<SpriteCell>,
+    ) {
+        let old_center = self.center_at([0, 0]);
+        let size = [columns.len(), rows.len()];
+        let mut cells = CellStorage::empty(self.storage(), size);
+        for (new_y, old_y) in rows.iter().enumerate() {
+            for (new_x, old_x) in columns.iter().enumerate() {
+                let cell = match (old_x, old_y) {
+                    (&Some(old_x), &Some(old_y)) => {
+                        let old = self.physical([old_x, old_y]);
+                        if self.sprite_cells.get(old).is_none() {
+                            continue;
+                        }
+                        self.sprite_cells.get_mut(old).take()
+                    }
+                    _ => fill.clone(),
+                };
+                if cell.is_some() {
+                    *cells.get_mut([new_x, new_y]) = cell;
+                }
+            }
+        }
+
+        let mut new_columns = vec![None; self.x_len];
+        for (new_x, old_x) in columns.iter().enumerate() {
+            if let &Some(old_x) = old_x {
+                new_columns[old_x] = Some(new_x);
+            }
+        }
+        let mut new_rows = vec![None; self.y_len];
+        for (new_y, old_y) in rows.iter().enumerate() {
+            if let &Some(old_y) = old_y {
+                new_rows[old_y] = Some(new_y);
+            }
+        }
+        // the new storage is contiguous, so the new cells are also the new storage positions
+        let moved = |physical: [usize; 2]| {
+            let [x, y] = self.logical(physical);
+            Some([new_columns[x]?, new_rows[y]?])
+        };
+        let cell_transforms = self
+            .cell_transforms
+            .iter()
+            .filter_map(|(&physical, &transform)| Some((moved(physical)?, transform)))
+            .collect();
+        // a multi-cell sprite survives if its columns and rows stay together
+        let together = |moves: &[Option<usize>], start: usize, len: usize| {
+            let new_start = moves[start]?;
+            (0..len)
+                .all(|i| moves.get(start + i) == Some(&Some(new_start + i)))
+                .then_some(new_start)
+        };
+        let mut footprints = HashMap::default();
+        for (&physical, &[w, h]) in &self.footprints {
+            let [x, y] = self.logical(physical);
+            match (together(&new_columns, x, w), together(&new_rows, y, h)) {
+                (Some(new_x), Some(new_y)) => {
+                    footprints.insert([new_x, new_y], [w, h]);
+                }
+                _ => {
+                    if let Some(origin) = moved(physical) {
+                        if cells.get(origin).is_some() {
+                            *cells.get_mut(origin) = None;
+                        }
+                    }
+                }
+            }
+        }
+        self.cell_transforms = cell_transforms;
+        self.footprints = footprints;
+
+        self.sprite_cells = cells;
+        self.origin = [0, 0];
+        [self.x_len, self.y_len] = size;
+        self.reindex_occupied();
+
+        self.scroll = [self.scroll[0] - delta[0], self.scroll[1] - delta[1]];
+        self.cell_offset += old_center - self.center_at(delta);
+    }
+
+    /// Resizes the grid to `[x_len, y_len]` cells.
+    /// Existing cells keep their indices and their positions in the world,
+    /// cells outside the new bounds are dropped and new cells are set to `fill`.
+    pub fn resize(&mut self, size: [usize; 2], fill: Option<SpriteCell>) {
+        self.resize_anchored(size, SpriteGridAlignment::bottom_left(), fill);
+    }
+
+    /// Resizes the grid to `[x_len, y_len]` cells, growing or shrinking
+    /// around `anchor`.
+    ///
+    /// With a `top_right` anchor the grid grows to the left and bottom,
+    /// so the existing cells are moved to higher indices. Cells pushed
+    /// outside the new bounds are dropped and new cells are set to `fill`.
+    ///
+    /// The existing cells keep their positions in the world. Moving a cell
+    /// to a higher index counts as scrolling the grid the other way,
+    /// see [`SpriteGrid::scroll`].
+    pub fn resize_anchored(
+        &mut self,
+        [x_len, y_len]: [usize; 2],
+        anchor: SpriteGridAlignment,
+        fill: Option<SpriteCell>,
+    ) {
+        let offset = |old_len: usize, new_len: usize, anchor: f32| {
+            (anchor * (new_len as f32 - old_len as f32)).round() as i64
+        };
+        let dx = offset(self.x_len, x_len, anchor.0.x);
+        let dy = offset(self.y_len, y_len, anchor.0.y);
+        let source = |new_len: usize, old_len: usize, delta: i64| {
+            (0..new_len)
+                .map(|i| {
+                    let old = i as i64 - delta;
+                    (0 <= old && old < old_len as i64).then_some(old as usize)
+                })
+                .collect::<Vec<_>>()
+        };
+        let columns = source(x_len, self.x_len, dx);
+        let rows = source(y_len, self.y_len, dy);
+        self.rebuild(columns, rows, [dx, dy], fill);
+    }
+
+    /// Inserts a row of `fill` cells at `y`, shifting the rows above it up.
+    pub fn insert_row(&mut self, y: usize, fill: Option<SpriteCell>) {
+        assert!(y <= self.y_len, "row {y} is outside of the sprite grid");
+        let columns = (0..self.x_len).map(Some).collect();
+        let rows = (0..=self.y_len)
+            .map(|i| match i.cmp(&y) {
+                Ordering::Less => Some(i),
+                Ordering::Equal => None,
+                Ordering::Greater => Some(i - 1),
+            })
+            .collect();
+        self.rebuild(columns, rows, [0, 0], fill);
+    }
+
+    /// Inserts a column of `fill` cells at `x`, shifting the columns to its right along.
+    pub fn insert_column(&mut self, x: usize, fill: Option<SpriteCell>) {
+        assert!(x <= self.x_len, "column {x} is outside of the sprite grid");
+        let columns = (0..=self.x_len)
+            .map(|i| match i.cmp(&x) {
+                Ordering::Less => Some(i),
+                Ordering::Equal => None,
+                Ordering::Greater => Some(i - 1),
+            })
+            .collect();
+        let rows = (0..self.y_len).map(Some).collect();
+        self.rebuild(columns, rows, [0, 0], fill);
+    }
+
+    /// Removes row `y`, shifting the rows above it down.
+    pub fn remove_row(&mut self, y: usize) {
+        assert!(y < self.y_len, "row {y} is outside of the sprite grid");
+        let columns = (0..self.x_len).map(Some).collect();
+        let rows = (0..self.y_len - 1)
+            .map(|i| Some(if i < y { i } else { i + 1 }))
+            .collect();
+        self.rebuild(columns, rows, [0, 0], None);
+    }
+
+    /// Removes column `x`, shifting the columns to its right back.
+    pub fn remove_column(&mut self, x: usize) {
+        assert!(x < self.x_len, "column {x} is outside of the sprite grid");
+        let columns = (0..self.x_len - 1)
+            .map(|i| Some(if i < x { i } else { i + 1 }))
+            .collect();
+        let rows = (0..self.y_len).map(Some).collect();
+        self.rebuild(columns, rows, [0, 0], None);
     }
 
     /// iterate through the non-empty sprite cells in the sub-grid
-    /// defined by the given ranges
+    /// defined by the given ranges, in row-major order
+    ///
+    /// Empty chunks of chunked grids are skipped without
+    /// visiting their cells.
     pub fn iter(
         &self,
         xs: Range<usize>,
         ys: Range<usize>,
     ) -> impl Iterator<Item = ([usize; 2], &SpriteCell)> {
-        xs.flat_map(move |x| {
-            ys.clone()
-                .filter_map(move |y| self[[x, y]].as_ref().map(|s| ([x, y], s)))
+        assert!(
+            xs.end <= self.x_len && ys.end <= self.y_len,
+            "iterated range is outside of the sprite grid"
+        );
+        ys.flat_map(move |y| {
+            let [_, physical_y] = self.physical([0, y]);
+            self.physical_columns(xs.clone())
+                .into_iter()
+                .flat_map(move |(x_start, physical_xs)| {
+                    let physical_start = physical_xs.start;
+                    self.sprite_cells
+                        .row_segments(physical_y, physical_xs)
+                        .flat_map(move |(physical_x, segment)| {
+                            let x0 = x_start + physical_x - physical_start;
+                            segment.iter().enumerate().filter_map(move |(i, cell)| {
+                                cell.as_ref().map(|s| ([x0 + i, y], s))
+                            })
+                        })
+                })
+        })
+    }
+
+    /// iterate through the non-empty sprite cells in `rect`, in row-major order
+    pub fn iter_rect(
+        &self,
+        rect: SpriteGridRect,
+    ) -> impl Iterator<Item = ([usize; 2], &SpriteCell)> {
+        self.iter(rect.xs(), rect.ys())
+    }
+
+    /// iterate through every cell in the sub-grid defined by the given ranges,
+    /// including the empty cells, in row-major order
+    pub fn iter_all(
+        &self,
+        xs: Range<usize>,
+        ys: Range<usize>,
+    ) -> impl Iterator<Item = ([usize; 2], &Option<SpriteCell>)> {
+        ys.flat_map(move |y| xs.clone().map(move |x| ([x, y], &self[[x, y]])))
+    }
+
+    /// every row of the grid from bottom to top,
+    /// each row is an iterator over its cells from left to right
+    pub fn rows(&self) -> impl Iterator<Item = impl Iterator<Item = &Option<SpriteCell>>> {
+        (0..self.y_len).map(move |y| (0..self.x_len).map(move |x| &self[[x, y]]))
+    }
+
+    /// every column of the grid from left to right,
+    /// each column is an iterator over its cells from bottom to top
+    pub fn columns(&self) -> impl Iterator<Item = impl Iterator<Item = &Option<SpriteCell>>> {
+        (0..self.x_len).map(move |x| self.column(x))
+    }
+
+    /// every cell in the given ranges, in row-major order
+    ///
+    /// Only the cells in the ranges are visited,
+    /// and empty chunks of chunked grids are skipped.
+    fn cells_mut_in(
+        &mut self,
+        xs: Range<usize>,
+        ys: Range<usize>,
+    ) -> impl Iterator<Item = ([usize; 2], &mut Option<SpriteCell>)> {
+        assert!(
+            xs.end <= self.x_len && ys.end <= self.y_len,
+            "iterated range is outside of the sprite grid"
+        );
+        let [x_len, y_len] = [self.x_len, self.y_len];
+        let [ox, oy] = self.origin;
+        // the storage ranges holding the logical range `start..start + len`
+        let wrap = |start: usize, len: usize, total: usize| {
+            let end = min(start + len, total);
+            [start..end, 0..len - (end - start)]
+        };
+        let [x_start, y_start] = self.physical([xs.start, ys.start]);
+        let columns = wrap(x_start, xs.len(), x_len);
+        let rows = wrap(y_start, ys.len(), y_len);
+        let logical = |physical: usize, origin: usize, len: usize| {
+            if origin <= physical {
+                physical - origin
+            } else {
+                physical + len - origin
+            }
+        };
+        let mut segments: Vec<_> = self
+            .sprite_cells
+            .row_segments_mut(&rows, &columns)
+            .into_iter()
+            .map(|([x, y], segment)| ([logical(x, ox, x_len), logical(y, oy, y_len)], segment))
+            .collect();
+        segments.sort_unstable_by_key(|&([x, y], _)| [y, x]);
+        segments.into_iter().flat_map(|([x0, y], segment)| {
+            segment
+                .iter_mut()
+                .enumerate()
+                .map(move |(i, cell)| ([x0 + i, y], cell))
         })
     }
+
+    /// iterate mutably through the non-empty sprite cells in the sub-grid
+    /// defined by the given ranges, in row-major order
+    pub fn iter_mut(
+        &mut self,
+        xs: Range<usize>,
+        ys: Range<usize>,
+    ) -> impl Iterator<Item = ([usize; 2], &mut SpriteCell)> {
+        self.cells_mut_in(xs, ys)
+            .filter_map(|(cell, sprite_cell)| sprite_cell.as_mut().map(|s| (cell, s)))
+    }
+
+    /// iterate mutably through the non-empty sprite cells in `rect`, in row-major order
+    pub fn iter_rect_mut(
+        &mut self,
+        rect: SpriteGridRect,
+    ) -> impl Iterator<Item = ([usize; 2], &mut SpriteCell)> {
+        self.iter_mut(rect.xs(), rect.ys())
+    }
+
+    /// Removes the non-empty sprite cells in `rect` and returns them, in row-major order.
+    /// The cells are removed even if the iterator isn't consumed.
+    pub fn drain(
+        &mut self,
+        rect: SpriteGridRect,
+    ) -> impl Iterator<Item = ([usize; 2], SpriteCell)> {
+        let drained: Vec<_> = self
+            .cells_mut_in(rect.xs(), rect.ys())
+            .filter_map(|(cell, sprite_cell)| sprite_cell.take().map(|s| (cell, s)))
+            .collect();
+        for &(cell, _) in &drained {
+            self.remove_footprint(self.physical(cell));
+        }
+        drained.into_iter()
+    }
+
+    /// Empties every sprite cell for which `f` returns false.
+    pub fn retain(&mut self, mut f: impl FnMut([usize; 2], &mut SpriteCell) -> bool) {
+        let bounds = self.bounds();
+        let mut removed = Vec::new();
+        for (cell, sprite_cell) in self.cells_mut_in(bounds.xs(), bounds.ys()) {
+            if let Some(s) = sprite_cell {
+                if !f(cell, s) {
+                    *sprite_cell = None;
+                    removed.push(cell);
+                }
+            }
+        }
+        for cell in removed {
+            self.remove_footprint(self.physical(cell));
+        }
+    }
+
+    /// The storage ranges holding the columns `xs`, paired with
+    /// the first column in each range.
+    fn physical_columns(&self, xs: Range<usize>) -> [(usize, Range<usize>); 2] {
+        let len = xs.len();
+        let [start, _] = self.physical([xs.start, 0]);
+        let first = start..min(start + len, self.x_len);
+        let wrapped = 0..len - first.len();
+        [(xs.start, first.clone()), (xs.start + first.len(), wrapped)]
+    }
 }
 
 impl Index<[usize; 2]> for SpriteGrid {
     type Output = Option<SpriteCell>;
 
-    fn index(&self, [x, y]: [usize; 2]) -> &Self::Output {
-        &self.sprite_cells[x][y]
+    fn index(&self, cell: [usize; 2]) -> &Self::Output {
+        self.check_bounds(cell);
+        self.sprite_cells.get(self.physical(cell))
     }
 }
 
+/// Allocates the cell's chunk if the grid is chunked.
+///
+/// Edits the cell in place, so a multi-cell sprite at the cell keeps its footprint.
+/// A multi-cell sprite whose origin is emptied is removed, use
+/// [`SpriteGrid::set`] to replace a multi-cell sprite with a single cell one.
+/// Indexing a cell occupied by a multi-cell sprite removes that sprite, so that
+/// the cell can be written to.
 impl IndexMut<[usize; 2]> for SpriteGrid {
-    fn index_mut(&mut self, [x, y]: [usize; 2]) -> &mut Self::Output {
-        &mut self.sprite_cells[x][y]
+    fn index_mut(&mut self, cell: [usize; 2]) -> &mut Self::Output {
+        self.check_bounds(cell);
+        let physical = self.physical(cell);
+        self.remove_occupant(physical);
+        // forget the footprint of an emptied origin before it can be refilled
+        if self.sprite_cells.get(physical).is_none() {
+            self.remove_footprint(physical);
+        }
+        self.sprite_cells.get_mut(physical)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cell that remembers where it was written.
+    fn marked([x, y]: [usize; 2]) -> SpriteCell {
+        SpriteCell::Color(Color::rgb(x as f32, y as f32, 0.0))
+    }
+
+    fn mark(cell: &Option<SpriteCell>) -> Option<[usize; 2]> {
+        match cell {
+            Some(SpriteCell::Color(color)) => Some([color.r() as usize, color.g() as usize]),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn cells_survive_storage_conversion() {
+        let written = [[0, 0], [69, 39], [31, 32], [32, 31], [40, 5]];
+        let mut grid = SpriteGrid::empty_chunked(([70, 40], Vec2::ONE));
+        for cell in written {
+            grid.set(cell, marked(cell));
+        }
+        assert_eq!(grid.allocated_chunks(), Some(4));
+        for storage in [SpriteGridStorage::Dense, SpriteGridStorage::Chunked] {
+            grid.set_storage(storage);
+            for y in 0..grid.y_len() {
+                for x in 0..grid.x_len() {
+                    let expected = written.contains(&[x, y]).then_some([x, y]);
+                    assert_eq!(mark(&grid[[x, y]]), expected);
+                }
+            }
+        }
+        // converting back only allocates the chunks holding cells
+        assert_eq!(grid.allocated_chunks(), Some(4));
+    }
+
+    #[test]
+    fn cells_and_overlays_survive_shifts() {
+        for storage in [SpriteGridStorage::Dense, SpriteGridStorage::Chunked] {
+            let mut grid = SpriteGrid::from_fn(([40, 35], Vec2::ONE), |cell| Some(marked(cell)));
+            grid.set_storage(storage);
+            grid.place([10, 30], [3, 2], marked([10, 30]));
+            grid.set_cell_transform([20, 34], Transform::from_xyz(1.0, 0.0, 0.0));
+
+            grid.shift([3, 30], None);
+            for y in 0..grid.y_len() {
+                for x in 0..grid.x_len() {
+                    let old = [x + 3, y + 30];
+                    // the multi-cell sprite emptied the other cells it covers
+                    let covered =
+                        (10..13).contains(&old[0]) && (30..32).contains(&old[1]) && old != [10, 30];
+                    let expected = (old[0] < 40 && old[1] < 35 && !covered).then_some(old);
+                    assert_eq!(mark(&grid[[x, y]]), expected, "{x} {y}");
+                }
+            }
+            assert_eq!(grid.owner([9, 1]), [7, 0]);
+            assert_eq!(grid.footprint([7, 0]), [3, 2]);
+            assert_eq!(grid.cell_transform([17, 4]).translation.x, 1.0);
+
+            // cells written while shifted are found again after shifting back
+            grid.set([0, 0], marked([99, 99]));
+            grid.shift([-3, -30], None);
+            assert_eq!(mark(&grid[[3, 30]]), Some([99, 99]));
+            assert_eq!(grid.owner([12, 31]), [10, 30]);
+            assert_eq!(grid.cell_transforms().count(), 1);
+            assert_eq!(grid.cell_transform([20, 34]).translation.x, 1.0);
+            assert_eq!(mark(&grid[[3, 29]]), None);
+            assert_eq!(grid.cells().is_some(), storage == SpriteGridStorage::Dense);
+            assert!(grid.footprints_are_consistent());
+        }
+    }
+
+    #[test]
+    fn multi_cells_shifted_partly_off_the_grid_are_removed() {
+        for [dx, dy] in [[-2, 0], [0, -2], [-2, -2], [3, 0], [0, 3]] {
+            let mut grid = SpriteGrid::from_fn(([6, 6], Vec2::ONE), |cell| Some(marked(cell)));
+            grid.place([2, 2], [3, 3], marked([2, 2]));
+            grid.place([0, 0], [1, 2], marked([0, 0]));
+            grid.shift([dx, dy], None);
+            assert!(grid.footprints_are_consistent(), "{dx} {dy}");
+            let multi_cells: Vec<_> = grid.multi_cells().collect();
+            match [dx, dy] {
+                // the sprite at [0, 0] is pushed along and stays whole
+                [-2, 0] => assert_eq!(multi_cells, vec![([2, 0], [1, 2])]),
+                [0, -2] => assert_eq!(multi_cells, vec![([0, 2], [1, 2])]),
+                [-2, -2] => assert_eq!(multi_cells, vec![([2, 2], [1, 2])]),
+                _ => assert_eq!(multi_cells, vec![]),
+            }
+            for y in 0..6 {
+                for x in 0..6 {
+                    if grid.owner([x, y]) != [x, y] {
+                        assert_eq!(mark(&grid[[x, y]]), None);
+                    } else if let Some([old_x, old_y]) = mark(&grid[[x, y]]) {
+                        // only the origin of the removed sprite is gone, the rest was already empty
+                        assert_ne!([old_x, old_y], [2, 2], "{dx} {dy}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn resizing_keeps_cells_in_place() {
+        for layout in [
+            SpriteGridLayout::Rectangular,
+            SpriteGridLayout::Hex(HexLayout::pointy_top(HexCoordinates::OddOffset)),
+            SpriteGridLayout::Hex(HexLayout::flat_top(HexCoordinates::EvenOffset)),
+            SpriteGridLayout::Isometric,
+            SpriteGridLayout::StaggeredIsometric(StaggerIndex::Odd),
+        ] {
+            let geometry = (
+                [7, 6],
+                Vec2::new(40.0, 46.0),
+                SpriteGridAlignment::center(),
+                layout,
+            );
+            let mut grid = SpriteGrid::from_fn(geometry, |cell| Some(marked(cell)));
+            let old_centers: Vec<Vec<Vec2>> = (0..7)
+                .map(|x| (0..6).map(|y| grid.cell_center([x, y])).collect())
+                .collect();
+
+            grid.resize_anchored([10, 3], SpriteGridAlignment::top_right(), None);
+            grid.insert_row(2, None);
+            // the rows above the inserted row move up, the cells below it keep their places
+            let mut survivors = 0;
+            for y in 0..2 {
+                for x in 0..grid.x_len() {
+                    if let Some([old_x, old_y]) = mark(&grid[[x, y]]) {
+                        let distance = grid.cell_center([x, y]).distance(old_centers[old_x][old_y]);
+                        assert!(distance < 1e-3, "{layout:?} {x} {y}");
+                        survivors += 1;
+                    }
+                }
+            }
+            assert_eq!(survivors, 7 * 2, "{layout:?}");
+            assert_eq!(grid.local_to_cell(grid.cell_center([5, 1])), [5, 1]);
+        }
+    }
+
+    #[test]
+    fn multi_cells_keep_their_cells_empty() {
+        let mut grid = SpriteGrid::from_fn(([8, 6], Vec2::ONE), |cell| Some(marked(cell)));
+        grid.place([1, 1], [3, 2], marked([1, 1]));
+        assert!(grid.footprints_are_consistent());
+        assert_eq!(grid.owner([3, 2]), [1, 1]);
+        assert!(grid.is_occupied([2, 1]) && !grid.is_occupied([1, 1]));
+        assert_eq!(mark(&grid[[3, 2]]), None);
+        assert_eq!(grid.sprite_size([1, 1]), Vec2::new(3.0, 2.0));
+
+        // placing over part of another multi-cell sprite removes it
+        grid.place([3, 2], [2, 2], marked([3, 2]));
+        assert!(grid.footprints_are_consistent());
+        assert_eq!(mark(&grid[[1, 1]]), None);
+        assert_eq!(grid.owner([2, 1]), [2, 1]);
+        assert_eq!(
+            grid.multi_cells().collect::<Vec<_>>(),
+            vec![([3, 2], [2, 2])]
+        );
+
+        // writing to an occupied cell removes the sprite occupying it
+        grid.set([4, 3], marked([4, 3]));
+        assert!(grid.footprints_are_consistent());
+        assert_eq!(grid.multi_cells().count(), 0);
+        assert_eq!(mark(&grid[[3, 2]]), None);
+
+        grid.place([5, 0], [2, 3], marked([5, 0]));
+        assert_eq!(mark(&grid.remove_multi_cell([6, 2])), Some([5, 0]));
+        assert!(grid.remove_multi_cell([6, 2]).is_none());
+        assert!(grid.footprints_are_consistent());
+        assert_eq!(grid.owner([6, 2]), [6, 2]);
+        assert_eq!(mark(&grid[[5, 0]]), None);
+
+        // emptying the origin through `IndexMut` removes the sprite, refilling it doesn't restore it
+        grid.place([0, 3], [2, 3], marked([0, 3]));
+        grid[[0, 3]] = None;
+        grid[[0, 3]] = Some(marked([0, 3]));
+        assert!(grid.footprints_are_consistent());
+        assert_eq!(grid.footprint([0, 3]), [1, 1]);
+        assert_eq!(grid.owner([1, 5]), [1, 5]);
+
+        // mutably indexing an occupied cell removes the sprite occupying it
+        grid.place([0, 3], [2, 3], marked([0, 3]));
+        grid[[1, 4]] = Some(marked([1, 4]));
+        assert!(grid.footprints_are_consistent());
+        assert_eq!(mark(&grid[[0, 3]]), None);
+        assert_eq!(grid.owner([1, 5]), [1, 5]);
+
+        // multi-cell sprites move with their cells, unless they are cut or split
+        grid.place([0, 3], [2, 3], marked([0, 3]));
+        grid.place([4, 0], [2, 2], marked([4, 0]));
+        grid.place([6, 4], [2, 2], marked([6, 4]));
+        grid.insert_row(2, None);
+        grid.insert_column(5, None);
+        grid.resize([8, 7], None);
+        assert!(grid.footprints_are_consistent());
+        assert_eq!(
+            grid.multi_cells().collect::<Vec<_>>(),
+            vec![([0, 4], [2, 3])]
+        );
+        assert_eq!(mark(&grid[[0, 4]]), Some([0, 3]));
+        assert_eq!(mark(&grid[[4, 0]]), None);
+    }
+
+    #[test]
+    fn mutable_iterators_match_indexing() {
+        let mut grid = SpriteGrid::empty_chunked(([70, 40], Vec2::ONE));
+        for y in 0..40 {
+            for x in (y % 7..70).step_by(7) {
+                grid.set([x, y], marked([x, y]));
+            }
+        }
+        // wraps the storage around in both directions
+        grid.shift([45, 33], None);
+
+        let rect = SpriteGridRect {
+            left: 3,
+            right: 24,
+            bottom: 2,
+            top: 7,
+        };
+        let expected: Vec<_> = grid
+            .iter_all(rect.xs(), rect.ys())
+            .filter_map(|(cell, sprite_cell)| Some((cell, mark(sprite_cell)?)))
+            .collect();
+        assert!(!expected.is_empty());
+        let chunks = grid.allocated_chunks();
+        let visited: Vec<_> = grid
+            .iter_rect_mut(rect)
+            .map(|(cell, sprite_cell)| (cell, mark(&Some(sprite_cell.clone())).unwrap()))
+            .collect();
+        assert_eq!(visited, expected);
+        assert_eq!(grid.allocated_chunks(), chunks);
+
+        for (cell, sprite_cell) in grid.iter_mut(0..70, 0..40) {
+            *sprite_cell = marked(cell);
+        }
+        for y in 0..40 {
+            for x in 0..70 {
+                if let Some(cell) = mark(&grid[[x, y]]) {
+                    assert_eq!(cell, [x, y]);
+                }
+            }
+        }
+        let expected: Vec<_> = (0..40).map(|y| mark(&grid[[5, y]])).collect();
+        let column: Vec<_> = grid.column_mut(5).map(|cell| mark(cell)).collect();
+        assert_eq!(column, expected);
+    }
+
+    #[test]
+    fn cell_depth_follows_the_sorting() {
+        let mut grid =
+            SpriteGrid::from_fn(([4, 5], Vec2::new(10.0, 20.0)), |cell| Some(marked(cell)));
+        assert_eq!(grid.cell_depth([2, 3]), 0.0);
+
+        // lower rows are in front, and every cell of a row is at the same depth
+        grid.sorting = SpriteGridSorting::ByRow;
+        for y in 0..5 {
+            let depth = grid.cell_depth([0, y]);
+            assert!((0.0..1.0).contains(&depth), "{y} {depth}");
+            for x in 1..4 {
+                assert_eq!(grid.cell_depth([x, y]), depth);
+            }
+            if 0 < y {
+                assert!(depth < grid.cell_depth([0, y - 1]), "{y}");
+            }
+        }
+
+        // isometric cells sort by the height of their centers too
+        let mut iso = SpriteGrid::empty((
+            [4, 4],
+            Vec2::new(20.0, 10.0),
+            SpriteGridAlignment::center(),
+            SpriteGridLayout::Isometric,
+        ));
+        iso.sorting = SpriteGridSorting::ByRow;
+        assert!(iso.cell_depth([1, 1]) < iso.cell_depth([0, 0]));
+        assert_eq!(iso.cell_depth([2, 1]), iso.cell_depth([1, 2]));
+
+        // empty cells get no offset from the key
+        grid.sorting = SpriteGridSorting::ByKey(|[x, y], _| (x + 10 * y) as f32);
+        grid[[1, 2]] = None;
+        assert_eq!(grid.cell_depth([3, 2]), 23.0);
+        assert_eq!(grid.cell_depth([1, 2]), 0.0);
     }
 }
@@ -0,0 +1,335 @@
+use crate::prelude::*;
+use std::cmp::max;
+use std::cmp::min;
+use std::ops::Range;
+
+/// Width and height in cells of the chunks used by
+/// [`SpriteGridStorage::Chunked`] grids.
+pub const CHUNK_SIZE: usize = 32;
+
+const CHUNK_LEN: usize = CHUNK_SIZE * CHUNK_SIZE;
+
+const EMPTY_CELL: &Option<SpriteCell> = &None;
+
+/// How a SpriteGrid stores its cells.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpriteGridStorage {
+    /// One contiguous row-major buffer holding every cell.
+    Dense,
+    /// Square chunks of [`CHUNK_SIZE`] cells that are only
+    /// allocated once one of their cells is written to.
+    /// Empty chunks cost a single pointer and are skipped when
+    /// iterating and culling.
+    Chunked,
+}
+
+impl Default for SpriteGridStorage {
+    fn default() -> Self {
+        Self::Dense
+    }
+}
+
+#[derive(Clone)]
+enum CellData {
+    Dense(Vec<Option<SpriteCell>>),
+    Chunked(Vec<Option<Box<[Option<SpriteCell>]>>>),
+}
+
+impl Default for CellData {
+    fn default() -> Self {
+        Self::Dense(Vec::new())
+    }
+}
+
+/// Cell storage of a SpriteGrid, indexed by `[x, y]`.
+/// Bounds checking is left to the grid.
+#[derive(Clone, Default)]
+pub(crate) struct CellStorage {
+    x_len: usize,
+    y_len: usize,
+    data: CellData,
+}
+
+fn chunk_count(len: usize) -> usize {
+    (len + CHUNK_SIZE - 1) / CHUNK_SIZE
+}
+
+fn allocate(chunk: &mut Option<Box<[Option<SpriteCell>]>>) -> &mut [Option<SpriteCell>] {
+    chunk.get_or_insert_with(|| vec![None; CHUNK_LEN].into_boxed_slice())
+}
+
+/// Calls `f` with each piece of `slice` covered by the non-overlapping `ranges`,
+/// paired with the index of its first element.
+fn split_ranges<'a, T>(
+    mut slice: &'a mut [T],
+    ranges: &[Range<usize>],
+    mut f: impl FnMut(usize, &'a mut [T]),
+) {
+    let mut ranges: Vec<_> = ranges.iter().filter(|range| !range.is_empty()).collect();
+    ranges.sort_unstable_by_key(|range| range.start);
+    let mut offset = 0;
+    for range in ranges {
+        let (_, rest) = std::mem::take(&mut slice).split_at_mut(range.start - offset);
+        let (piece, rest) = rest.split_at_mut(range.len());
+        f(range.start, piece);
+        slice = rest;
+        offset = range.end;
+    }
+}
+
+/// Every `stride`th cell starting from `cells[start]`.
+fn strided(
+    cells: &mut [Option<SpriteCell>],
+    start: usize,
+    stride: usize,
+) -> impl Iterator<Item = &mut Option<SpriteCell>> {
+    cells.iter_mut().skip(start).step_by(stride)
+}
+
+/// The cells of column `x` in a band of whole chunk rows, allocating its chunks.
+fn chunk_column(
+    chunks: &mut [Option<Box<[Option<SpriteCell>]>>],
+    x: usize,
+    chunks_x: usize,
+) -> impl Iterator<Item = &mut Option<SpriteCell>> {
+    chunks
+        .iter_mut()
+        .skip(x / CHUNK_SIZE)
+        .step_by(chunks_x)
+        .flat_map(move |chunk| strided(allocate(chunk), x % CHUNK_SIZE, CHUNK_SIZE))
+}
+
+impl CellStorage {
+    pub fn dense([x_len, y_len]: [usize; 2], cells: Vec<Option<SpriteCell>>) -> Self {
+        debug_assert_eq!(cells.len(), x_len * y_len);
+        Self {
+            x_len,
+            y_len,
+            data: CellData::Dense(cells),
+        }
+    }
+
+    pub fn chunked([x_len, y_len]: [usize; 2]) -> Self {
+        Self {
+            x_len,
+            y_len,
+            data: CellData::Chunked(vec![None; chunk_count(x_len) * chunk_count(y_len)]),
+        }
+    }
+
+    pub fn empty(kind: SpriteGridStorage, size: [usize; 2]) -> Self {
+        match kind {
+            SpriteGridStorage::Dense => Self::dense(size, vec![None; size[0] * size[1]]),
+            SpriteGridStorage::Chunked => Self::chunked(size),
+        }
+    }
+
+    pub fn kind(&self) -> SpriteGridStorage {
+        match self.data {
+            CellData::Dense(_) => SpriteGridStorage::Dense,
+            CellData::Chunked(_) => SpriteGridStorage::Chunked,
+        }
+    }
+
+    fn chunk_index(&self, [x, y]: [usize; 2]) -> (usize, usize) {
+        let chunk = (y / CHUNK_SIZE) * chunk_count(self.x_len) + x / CHUNK_SIZE;
+        let cell = (y % CHUNK_SIZE) * CHUNK_SIZE + x % CHUNK_SIZE;
+        (chunk, cell)
+    }
+
+    pub fn get(&self, [x, y]: [usize; 2]) -> &Option<SpriteCell> {
+        match &self.data {
+            CellData::Dense(cells) => &cells[y * self.x_len + x],
+            CellData::Chunked(chunks) => {
+                let (chunk, cell) = self.chunk_index([x, y]);
+                chunks[chunk]
+                    .as_ref()
+                    .map_or(EMPTY_CELL, |chunk| &chunk[cell])
+            }
+        }
+    }
+
+    /// Allocates the cell's chunk if it is empty.
+    pub fn get_mut(&mut self, [x, y]: [usize; 2]) -> &mut Option<SpriteCell> {
+        let (chunk, cell) = self.chunk_index([x, y]);
+        match &mut self.data {
+            CellData::Dense(cells) => &mut cells[y * self.x_len + x],
+            CellData::Chunked(chunks) => &mut allocate(&mut chunks[chunk])[cell],
+        }
+    }
+
+    pub fn as_slice(&self) -> Option<&[Option<SpriteCell>]> {
+        match &self.data {
+            CellData::Dense(cells) => Some(cells),
+            CellData::Chunked(_) => None,
+        }
+    }
+
+    pub fn as_mut_slice(&mut self) -> Option<&mut [Option<SpriteCell>]> {
+        match &mut self.data {
+            CellData::Dense(cells) => Some(cells),
+            CellData::Chunked(_) => None,
+        }
+    }
+
+    fn segment_width(&self) -> usize {
+        match self.data {
+            CellData::Dense(_) => max(self.x_len, 1),
+            CellData::Chunked(_) => CHUNK_SIZE,
+        }
+    }
+
+    /// The contiguous pieces of row `y` within `xs`, paired with the `x`
+    /// of their first cell. Pieces of empty chunks are skipped.
+    pub fn row_segments(
+        &self,
+        y: usize,
+        xs: Range<usize>,
+    ) -> impl Iterator<Item = (usize, &[Option<SpriteCell>])> {
+        let width = self.segment_width();
+        let first = xs.start / width;
+        let last = (xs.end + width - 1) / width;
+        (first..last).filter_map(move |segment| {
+            let offset = segment * width;
+            let start = max(xs.start, offset);
+            let end = min(xs.end, offset + width);
+            let row = match &self.data {
+                CellData::Dense(cells) => &cells[y * self.x_len..(y + 1) * self.x_len],
+                CellData::Chunked(chunks) => {
+                    let chunk = (y / CHUNK_SIZE) * chunk_count(self.x_len) + segment;
+                    let row = (y % CHUNK_SIZE) * CHUNK_SIZE;
+                    &chunks[chunk].as_ref()?[row..row + CHUNK_SIZE]
+                }
+            };
+            Some((start, &row[start - offset..end - offset]))
+        })
+    }
+
+    /// The contiguous pieces of the rows in `ys` within the columns in `xs`,
+    /// paired with the `[x, y]` of their first cell, in no particular order.
+    /// The ranges of `ys` and of `xs` mustn't overlap. Pieces of empty chunks are skipped.
+    pub fn row_segments_mut(
+        &mut self,
+        ys: &[Range<usize>],
+        xs: &[Range<usize>],
+    ) -> Vec<([usize; 2], &mut [Option<SpriteCell>])> {
+        let x_len = self.x_len;
+        let y_len = self.y_len;
+        let mut segments = Vec::new();
+        match &mut self.data {
+            CellData::Dense(cells) => {
+                let bands: Vec<_> = ys
+                    .iter()
+                    .map(|ys| ys.start * x_len..ys.end * x_len)
+                    .collect();
+                split_ranges(cells, &bands, |start, band| {
+                    for (i, row) in band.chunks_mut(x_len).enumerate() {
+                        let y = start / x_len + i;
+                        split_ranges(row, xs, |x, segment| segments.push(([x, y], segment)));
+                    }
+                });
+            }
+            CellData::Chunked(chunks) => {
+                let chunks_x = chunk_count(x_len);
+                // the parts of `ranges` inside `offset..offset + len`, relative to `offset`
+                let local = |ranges: &[Range<usize>], offset: usize, len: usize| -> Vec<_> {
+                    ranges
+                        .iter()
+                        .map(|range| {
+                            let clamp = |i: usize| i.clamp(offset, offset + len) - offset;
+                            clamp(range.start)..clamp(range.end)
+                        })
+                        .filter(|range| !range.is_empty())
+                        .collect()
+                };
+                for (index, chunk) in chunks.iter_mut().enumerate() {
+                    let x0 = (index % chunks_x) * CHUNK_SIZE;
+                    let y0 = (index / chunks_x) * CHUNK_SIZE;
+                    let chunk = match chunk {
+                        Some(chunk) => chunk,
+                        None => continue,
+                    };
+                    let chunk_xs = local(xs, x0, min(CHUNK_SIZE, x_len - x0));
+                    let chunk_ys = local(ys, y0, min(CHUNK_SIZE, y_len - y0));
+                    if chunk_xs.is_empty() || chunk_ys.is_empty() {
+                        continue;
+                    }
+                    let bands: Vec<_> = chunk_ys
+                        .iter()
+                        .map(|ys| ys.start * CHUNK_SIZE..ys.end * CHUNK_SIZE)
+                        .collect();
+                    split_ranges(chunk, &bands, |start, band| {
+                        for (i, row) in band.chunks_mut(CHUNK_SIZE).enumerate() {
+                            let y = y0 + start / CHUNK_SIZE + i;
+                            split_ranges(row, &chunk_xs, |x, segment| {
+                                segments.push(([x0 + x, y], segment))
+                            });
+                        }
+                    });
+                }
+            }
+        }
+        segments
+    }
+
+    /// Mutable references to the cells of column `x`, ordered by `y`
+    /// starting from row `y_start` and wrapping around to row `0`.
+    /// Allocates any empty chunks in the column.
+    pub fn column_mut(
+        &mut self,
+        x: usize,
+        y_start: usize,
+    ) -> Box<dyn Iterator<Item = &mut Option<SpriteCell>> + '_> {
+        let x_len = self.x_len;
+        let y_len = self.y_len;
+        if y_len == 0 {
+            return Box::new(std::iter::empty());
+        }
+        match &mut self.data {
+            CellData::Dense(cells) => {
+                let (below, above) = cells.split_at_mut(y_start * x_len);
+                Box::new(strided(above, x, x_len).chain(strided(below, x, x_len)))
+            }
+            CellData::Chunked(chunks) => {
+                let chunks_x = chunk_count(x_len);
+                let (below, rest) = chunks.split_at_mut(y_start / CHUNK_SIZE * chunks_x);
+                let (split, above) = rest.split_at_mut(chunks_x);
+                // the chunk holding `y_start` is split between the two halves
+                let (split_below, split_above) = allocate(&mut split[x / CHUNK_SIZE])
+                    .split_at_mut(y_start % CHUNK_SIZE * CHUNK_SIZE);
+                let chunk_x = x % CHUNK_SIZE;
+                Box::new(
+                    strided(split_above, chunk_x, CHUNK_SIZE)
+                        .chain(chunk_column(above, x, chunks_x))
+                        .take(y_len - y_start)
+                        .chain(chunk_column(below, x, chunks_x))
+                        .chain(strided(split_below, chunk_x, CHUNK_SIZE)),
+                )
+            }
+        }
+    }
+
+    /// Frees every chunk that has no non-empty cells.
+    pub fn release_empty_chunks(&mut self) {
+        if let CellData::Chunked(chunks) = &mut self.data {
+            for chunk in chunks.iter_mut() {
+                if chunk
+                    .as_ref()
+                    .map_or(false, |cells| cells.iter().all(Option::is_none))
+                {
+                    *chunk = None;
+                }
+            }
+        }
+    }
+
+    /// Number of allocated chunks, or `None` for dense storage.
+    pub fn allocated_chunks(&self) -> Option<usize> {
+        match &self.data {
+            CellData::Dense(_) => None,
+            CellData::Chunked(chunks) => {
+                Some(chunks.iter().filter(|chunk| chunk.is_some()).count())
+            }
+        }
+    }
+}
@@ -4,7 +4,7 @@ use std::ops::Range;
 
 use bevy::prelude::*;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct SpriteGridRect {
     pub left: usize,
     pub right: usize,
@@ -21,6 +21,18 @@ impl SpriteGridRect {
         self.bottom..self.top
     }
 
+    pub fn width(self) -> usize {
+        self.right.saturating_sub(self.left)
+    }
+
+    pub fn height(self) -> usize {
+        self.top.saturating_sub(self.bottom)
+    }
+
+    pub fn contains(self, [x, y]: [usize; 2]) -> bool {
+        self.xs().contains(&x) && self.ys().contains(&y)
+    }
+
     pub fn intersect_with(self, other: Self) -> Option<Self> {
         let left = max(self.left, other.left);
         let right = min(self.right, other.right);